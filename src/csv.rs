@@ -2,5 +2,7 @@ pub mod agglomerate;
 pub mod extract;
 pub mod extract_dialogues;
 pub mod fuse;
+pub mod merge;
 pub mod repack_dialogues;
 pub mod separate;
+pub mod sheet;
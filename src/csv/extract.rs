@@ -8,12 +8,39 @@ use csv::Writer;
 use crate::{
     PlaceholderOrCharacter,
     helpers::offset_wrapper::OffsetReadWrapper,
-    mbe::{MBEFile, TableCell},
+    mbe::{ColumnRole, MBEFile, SchemaRegistry, TableCell},
 };
 
+///
+/// Parses an MBE file from `source` and writes its dialogues as CSV, like
+/// [`extract_file_as_csv`].
+///
 pub fn extract_as_csv(
     source: &mut dyn Read,
     destination: &mut Writer<&mut dyn Write>,
+    schemas: &SchemaRegistry,
+    translated_name: Option<&[u8]>,
+    file_language_name: Option<&[u8]>,
+) -> io::Result<()> {
+    let file = MBEFile::parse(&mut OffsetReadWrapper::new(source)).map_err(io::Error::other)?;
+    extract_file_as_csv(
+        &file,
+        destination,
+        schemas,
+        translated_name,
+        file_language_name,
+    )
+}
+
+///
+/// Writes the dialogues of an MBE file as CSV. The columns holding the ID, the speaker and
+/// the text of each dialogue are taken from `schemas`, and sheets without a known schema
+/// are skipped.
+///
+pub fn extract_file_as_csv(
+    file: &MBEFile,
+    destination: &mut Writer<&mut dyn Write>,
+    schemas: &SchemaRegistry,
     translated_name: Option<&[u8]>,
     file_language_name: Option<&[u8]>,
 ) -> io::Result<()> {
@@ -23,21 +50,40 @@ pub fn extract_as_csv(
         translated_name.unwrap_or(b"Translated"),
         file_language_name.unwrap_or(b"Original"),
     ])?;
-    let file = MBEFile::parse(&mut OffsetReadWrapper::new(source)).unwrap();
-    for row in file.rows() {
-        let (character, message) = match row.get(1) {
-            Some(&TableCell::Int(x)) => (
-                PlaceholderOrCharacter::from(x).name(),
-                row[2].unwrap_string(),
-            ),
-            _ => (Cow::Borrowed(""), row[1].unwrap_string()),
+    for sheet in (0..file.number_of_sheets()).flat_map(|x| file.get_sheet_by_index(x)) {
+        let Some(schema) = schemas.find(sheet.name(), sheet.column_types()) else {
+            log::warn!(
+                "skipping sheet {:?}, which has no known schema",
+                sheet.name()
+            );
+            continue;
         };
-        destination.write_record([
-            row[0].to_string().as_bytes(),
-            character.as_bytes(),
-            b"",
-            message.map_or(b"", |x| &x.0),
-        ])?;
+        let (Some(id), Some(text)) = (
+            schema.column(ColumnRole::MessageId),
+            schema.column(ColumnRole::Text),
+        ) else {
+            log::warn!("skipping sheet {:?}, which holds no dialogue", sheet.name());
+            continue;
+        };
+        let speaker = schema.column(ColumnRole::Speaker);
+
+        for row in (0..sheet.number_of_row()).flat_map(|x| sheet.get_row(x)) {
+            let row = row.content();
+            let character = match speaker.map(|x| row[x]) {
+                Some(TableCell::Int(x)) => PlaceholderOrCharacter::from(x).name(),
+                _ => Cow::Borrowed(""),
+            };
+            let message = match row[text] {
+                TableCell::String(x) | TableCell::StringID(x) => x,
+                _ => None,
+            };
+            destination.write_record([
+                row[id].to_string().as_bytes(),
+                character.as_bytes(),
+                b"",
+                message.map_or(b"", |x| &x.0),
+            ])?;
+        }
     }
     Ok(())
 }
@@ -1,44 +1,88 @@
 use std::{
-    borrow::Cow,
-    ffi::OsStr,
     fs::{self, File},
-    io::{self, BufReader, Write},
+    io::{self, Write},
     path::Path,
-    time::Duration,
+    sync::Arc,
 };
 
 use csv::Writer;
-use indicatif::{MultiProgress, ProgressBar, ProgressFinish, ProgressIterator};
+#[cfg(feature = "indicatif")]
+use indicatif::MultiProgress;
+use rayon::iter::ParallelIterator;
 use tempfile::TempDir;
-use walkdir::WalkDir;
 
 use crate::{
-    helpers::indicatif::{
-        IndicatifProgressExt, default_bar_style_with_message_header, default_spinner_style,
-        default_spinner_style_with_message_header,
+    helpers::{
+        cancellation::{CancellationToken, check_cancellation},
+        progress::{ProgressReporter, ProgressUnit, default_progress_reporter},
     },
-    mvgl::Extractor,
+    mbe::SchemaRegistry,
+    mvgl::MVGLArchive,
 };
 
-pub struct DialogueExtractor<'a> {
-    multi_progress: Option<&'a MultiProgress>,
+pub struct DialogueExtractor {
+    progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    cancellation_token: Option<CancellationToken>,
+    schemas: Option<Arc<SchemaRegistry>>,
 }
 
-impl Default for DialogueExtractor<'_> {
+impl Default for DialogueExtractor {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'a> DialogueExtractor<'a> {
+impl DialogueExtractor {
     pub const fn new() -> Self {
         Self {
-            multi_progress: None,
+            progress_reporter: None,
+            cancellation_token: None,
+            schemas: None,
         }
     }
 
-    pub const fn with_multi_progress(multi_progress: Option<&'a MultiProgress>) -> Self {
-        Self { multi_progress }
+    #[cfg(feature = "indicatif")]
+    pub fn with_multi_progress(multi_progress: Option<&MultiProgress>) -> Self {
+        Self::new().with_progress_reporter(Some(Arc::new(
+            crate::helpers::progress::IndicatifProgress::new(
+                multi_progress.cloned().unwrap_or_default(),
+            ),
+        )))
+    }
+
+    ///
+    /// Sets where the progress of the extraction is reported. Defaults to
+    /// [`IndicatifProgress`](crate::helpers::progress::IndicatifProgress) if the `indicatif`
+    /// feature is enabled, and to nothing otherwise.
+    ///
+    pub fn with_progress_reporter(
+        self,
+        progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    ) -> Self {
+        Self {
+            progress_reporter,
+            ..self
+        }
+    }
+
+    ///
+    /// Sets a token that can be used to stop the extraction between two files.
+    ///
+    /// Nothing is written to the destination if the extraction is cancelled.
+    ///
+    pub fn with_cancellation_token(self, cancellation_token: Option<CancellationToken>) -> Self {
+        Self {
+            cancellation_token,
+            ..self
+        }
+    }
+
+    ///
+    /// Sets the schemas telling which columns hold the dialogues to be extracted. Defaults to
+    /// [`SchemaRegistry::default`].
+    ///
+    pub fn with_schemas(self, schemas: Option<Arc<SchemaRegistry>>) -> Self {
+        Self { schemas, ..self }
     }
 
     pub fn extract(
@@ -46,101 +90,83 @@ impl<'a> DialogueExtractor<'a> {
         languages: &[(impl AsRef<Path>, impl AsRef<str>)],
         destination: &mut dyn Write,
     ) -> io::Result<()> {
-        let extraction_dir = TempDir::new()?;
-
         let languages_dir = std::iter::from_fn(|| Some(TempDir::new()))
             .take(languages.len())
             .collect::<Result<Vec<_>, _>>()?;
 
-        let multi_progress = self
-            .multi_progress
-            .map_or_else(|| Cow::Owned(MultiProgress::default()), Cow::Borrowed);
-
-        let progress_bar = ProgressBar::new(languages.len() as u64)
-            .with_style(default_bar_style_with_message_header("working on language"));
-
-        for ((lang_path, lang_name), extracted_language_dir) in languages
-            .iter()
-            .zip(&languages_dir)
-            .progress_with(progress_bar.clone())
-            .in_multi_progress(&multi_progress)
-            .with_finish(ProgressFinish::WithMessage(
-                "finished extracting all files".into(),
-            ))
-        {
-            progress_bar.set_message(lang_name.as_ref().to_string());
-            Extractor::new()
-                .with_multi_progress(Some(&multi_progress))
-                .extract(
-                    &mut BufReader::new(File::open(lang_path)?),
-                    extracted_language_dir.path(),
-                )?;
-
-            let spinner = ProgressBar::new_spinner().with_style(
-                default_spinner_style_with_message_header("creating individual CSV for"),
+        let progress_reporter = self
+            .progress_reporter
+            .clone()
+            .unwrap_or_else(default_progress_reporter);
+        let schemas = self.schemas.clone().unwrap_or_default();
+
+        let progress_bar = progress_reporter.start_phase(
+            "working on language",
+            Some(languages.len() as u64),
+            ProgressUnit::Items,
+        );
+
+        for ((lang_path, lang_name), language_dir) in languages.iter().zip(&languages_dir) {
+            check_cancellation(self.cancellation_token.as_ref())?;
+            let lang_name = lang_name.as_ref();
+            progress_bar.set_message(lang_name);
+            let archive = MVGLArchive::from_path(lang_path)?;
+
+            let spinner = progress_reporter.start_phase(
+                "creating individual CSV for",
+                None,
+                ProgressUnit::Items,
             );
-            multi_progress.add(spinner.clone());
-            spinner.enable_steady_tick(Duration::from_millis(200));
-
-            for file in WalkDir::new(extracted_language_dir) {
-                let file = file?;
-                //if (!file
-                //    .path()
-                //    .ends_with("s15_a_deathgam_aaaab_d078_m020_010.mbe"))
-                //{
-                //    continue;
-                //}
-                if file.file_type().is_dir() {
-                    fs::create_dir_all(extraction_dir.path().join(file.path()))?;
-                    continue;
-                }
-                if file.path().extension() != Some(OsStr::new("mbe")) {
-                    continue;
-                }
-
-                spinner.set_message(
-                    file.path()
-                        .strip_prefix(extracted_language_dir.path())
-                        .unwrap()
-                        .display()
-                        .to_string(),
-                );
-
-                let p = extracted_language_dir
-                    .path()
-                    .join(file.path().with_extension("csv"));
-                let mut destination = File::create_new(p)?;
-
-                let p = extracted_language_dir.path().join(file.path());
-                crate::csv::extract::extract_as_csv(
-                    &mut BufReader::new(File::open(p)?),
-                    &mut Writer::from_writer(&mut destination),
-                    Some(b"Translated".as_slice()),
-                    Some(lang_name.as_ref().as_bytes()),
-                )?;
-            }
+
+            archive
+                .iter_mbe()
+                .try_for_each(|(path, file)| -> io::Result<()> {
+                    check_cancellation(self.cancellation_token.as_ref())?;
+                    spinner.set_message(path);
+                    let file = file.map_err(io::Error::other)?;
+
+                    let p = language_dir
+                        .path()
+                        .join(Path::new(path).with_extension("csv"));
+                    if let Some(parent) = p.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let mut destination = File::create_new(p)?;
+                    crate::csv::extract::extract_file_as_csv(
+                        &file,
+                        &mut Writer::from_writer(&mut destination),
+                        &schemas,
+                        Some(b"Translated".as_slice()),
+                        Some(lang_name.as_bytes()),
+                    )
+                })?;
+            spinner.finish("finished creating individual CSVs");
+            progress_bar.advance(1);
         }
+        progress_bar.finish("finished extracting all files");
 
         let (main, other) = languages_dir.split_first().unwrap();
 
-        let progress_bar = ProgressBar::new_spinner()
-            .with_style(default_spinner_style())
-            .with_message("fusing CSVs");
-        progress_bar.enable_steady_tick(Duration::from_millis(200));
+        let progress_bar = progress_reporter.start_phase(
+            "fusing CSVs",
+            Some(other.len() as u64),
+            ProgressUnit::Items,
+        );
         for dir in other {
+            check_cancellation(self.cancellation_token.as_ref())?;
             let tmp = main.path().with_extension("fuse-tmp");
             crate::csv::fuse::fuse_csv(main.path(), dir.path(), tmp.as_path())?;
             fs::remove_dir_all(main.path())?;
             fs::rename(tmp.as_path(), main.path())?;
+            progress_bar.advance(1);
         }
-        progress_bar.finish_and_clear();
+        progress_bar.finish("finished fusing CSVs");
 
-        let progress_bar = ProgressBar::new_spinner()
-            .with_style(default_spinner_style())
-            .with_message("agglomerating CSVs");
-        progress_bar.enable_steady_tick(Duration::from_millis(200));
+        check_cancellation(self.cancellation_token.as_ref())?;
+        let progress_bar =
+            progress_reporter.start_phase("agglomerating CSVs", None, ProgressUnit::Items);
         crate::csv::agglomerate::agglomerate_csv(main.path(), destination)?;
-        progress_bar.finish_and_clear();
+        progress_bar.finish("finished agglomerating CSVs");
 
         Ok(())
     }
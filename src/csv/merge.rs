@@ -0,0 +1,224 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use csv::{ByteRecord, Reader, Writer};
+
+// The columns of the CSV written by `DialogueExtractor`: the ID, the character, the
+// translation, the original text in each language and the file name
+const ID_COLUMN: usize = 0;
+const TRANSLATED_COLUMN: usize = 2;
+const FIRST_TEXT_COLUMN: usize = 3;
+
+///
+/// What the translations needing review start with in the merged CSV, so that they can be
+/// found in it. It's removed by [`DialogueRepacker`](super::repack_dialogues::DialogueRepacker)
+/// if it's left there.
+///
+pub const NEEDS_REVIEW_MARKER: &str = "[needs review] ";
+
+#[derive(Debug)]
+pub enum MergeTranslationsError {
+    Csv(csv::Error),
+    /// A CSV doesn't have the columns written by
+    /// [`DialogueExtractor`](super::extract_dialogues::DialogueExtractor).
+    InvalidHeader {
+        source: &'static str,
+    },
+    /// The old and the new originals aren't in the same languages.
+    LanguageMismatch {
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+}
+
+impl Display for MergeTranslationsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Csv(x) => write!(f, "csv error: {x}"),
+            Self::InvalidHeader { source } => write!(
+                f,
+                "the {source} should have the columns of an extracted dialogue CSV"
+            ),
+            Self::LanguageMismatch { old, new } => write!(
+                f,
+                "the old original is in {old:?}, but the new original is in {new:?}"
+            ),
+        }
+    }
+}
+impl std::error::Error for MergeTranslationsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Csv(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for MergeTranslationsError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+///
+/// A dialogue of an extracted CSV, identified by the file it comes from and its ID.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageKey {
+    pub file_name: String,
+    pub id: String,
+}
+
+impl MessageKey {
+    fn of(record: &ByteRecord) -> Self {
+        Self {
+            file_name: String::from_utf8_lossy(&record[record.len() - 1]).into_owned(),
+            id: String::from_utf8_lossy(&record[ID_COLUMN]).into_owned(),
+        }
+    }
+}
+
+impl Display for MessageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, ID {}", self.file_name, self.id)
+    }
+}
+
+///
+/// What [`merge_translations`] did with each dialogue.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// How many translations were kept as is, their original being unchanged.
+    pub kept: usize,
+    /// The dialogues whose original changed or isn't in the old original, which kept their
+    /// translation preceded by [`NEEDS_REVIEW_MARKER`].
+    pub needs_review: Vec<MessageKey>,
+    /// How many dialogues were left untranslated, being new or having no translation.
+    pub untranslated: usize,
+    /// How many dialogues were dropped, not being in the new original anymore.
+    pub removed: usize,
+}
+
+///
+/// Reads every record of `source`, checking it has at least the columns of an extracted CSV:
+/// one original text and the file name after the translation.
+///
+fn read_records(
+    source: &mut dyn Read,
+    name: &'static str,
+) -> Result<(ByteRecord, Vec<ByteRecord>), MergeTranslationsError> {
+    let mut source = Reader::from_reader(source);
+    let header = source.byte_headers()?.clone();
+    if header.len() < FIRST_TEXT_COLUMN + 2 {
+        return Err(MergeTranslationsError::InvalidHeader { source: name });
+    }
+    let records = source.byte_records().collect::<Result<Vec<_>, _>>()?;
+    Ok((header, records))
+}
+
+/// The original texts of a record, without the file name.
+fn texts(record: &ByteRecord) -> impl Iterator<Item = &[u8]> {
+    record.iter().take(record.len() - 1).skip(FIRST_TEXT_COLUMN)
+}
+
+/// The records by key. Records sharing a key are kept in order, to be matched in order.
+fn by_key(records: Vec<ByteRecord>) -> HashMap<MessageKey, VecDeque<ByteRecord>> {
+    let mut map = HashMap::<_, VecDeque<_>>::new();
+    for record in records {
+        map.entry(MessageKey::of(&record))
+            .or_default()
+            .push_back(record);
+    }
+    map
+}
+
+///
+/// Carries a translation over to a new version of the game.
+///
+/// `old_original` and `new_original` are the dialogues of the game before and after the
+/// update, and `translation` the translated dialogues of the old version, all in the layout
+/// written by [`DialogueExtractor`](super::extract_dialogues::DialogueExtractor). Dialogues
+/// are matched by file name and ID, and `destination` gets the dialogues of `new_original`
+/// where:
+///
+/// - dialogues whose original didn't change keep their translation,
+/// - dialogues whose original changed keep their old translation, preceded by
+///   [`NEEDS_REVIEW_MARKER`], and are reported as needing review,
+/// - translated dialogues missing from `old_original`, whose original can't be compared,
+///   are handled as if it changed,
+/// - new dialogues are left untranslated,
+///
+/// while the dialogues that aren't in `new_original` anymore are dropped.
+///
+pub fn merge_translations(
+    old_original: &mut dyn Read,
+    new_original: &mut dyn Read,
+    translation: &mut dyn Read,
+    destination: &mut dyn Write,
+) -> Result<MergeReport, MergeTranslationsError> {
+    let (old_header, old_records) = read_records(old_original, "old original")?;
+    let (new_header, new_records) = read_records(new_original, "new original")?;
+    let (_, translated_records) = read_records(translation, "translation")?;
+    if texts(&old_header).ne(texts(&new_header)) {
+        let languages = |header: &ByteRecord| {
+            texts(header)
+                .map(|x| String::from_utf8_lossy(x).into_owned())
+                .collect()
+        };
+        return Err(MergeTranslationsError::LanguageMismatch {
+            old: languages(&old_header),
+            new: languages(&new_header),
+        });
+    }
+
+    let mut old_records = by_key(old_records);
+    let mut translations = by_key(translated_records);
+    let mut report = MergeReport::default();
+    let mut destination = Writer::from_writer(destination);
+    destination.write_byte_record(&new_header)?;
+
+    for record in new_records {
+        let key = MessageKey::of(&record);
+        let old = old_records.get_mut(&key).and_then(VecDeque::pop_front);
+        let translated = translations.get_mut(&key).and_then(VecDeque::pop_front);
+        let translated = translated
+            .as_ref()
+            .map_or(b"".as_slice(), |x| &x[TRANSLATED_COLUMN]);
+
+        let translated = match old {
+            _ if translated.is_empty() => {
+                report.untranslated += 1;
+                Vec::new()
+            }
+            Some(old) if texts(&old).eq(texts(&record)) => {
+                report.kept += 1;
+                translated.to_vec()
+            }
+            _ => {
+                report.needs_review.push(key);
+                let marker = NEEDS_REVIEW_MARKER.as_bytes();
+                let unmarked = translated.strip_prefix(marker).unwrap_or(translated);
+                [marker, unmarked].concat()
+            }
+        };
+
+        let mut merged = ByteRecord::with_capacity(record.as_slice().len(), record.len());
+        for (column, field) in record.iter().enumerate() {
+            merged.push_field(if column == TRANSLATED_COLUMN {
+                &translated
+            } else {
+                field
+            });
+        }
+        destination.write_byte_record(&merged)?;
+    }
+    destination.flush().map_err(csv::Error::from)?;
+
+    report.removed = old_records.values().map(VecDeque::len).sum();
+    Ok(report)
+}
@@ -1,49 +1,108 @@
 use std::{
     fs::{self, File},
     io::{self, BufWriter, Read},
+    path::Path,
+    sync::Arc,
 };
 
 use atoi::atoi;
 use byte_string::ByteStr;
 use csv::Reader;
+#[cfg(feature = "indicatif")]
 use indicatif::MultiProgress;
 use tempfile::TempDir;
 use walkdir::WalkDir;
 
 use crate::{
+    csv::merge::NEEDS_REVIEW_MARKER,
     helpers::{
+        atomic_file::write_atomically,
+        cancellation::{CancellationToken, check_cancellation},
         offset_wrapper::OffsetWriteWrapper,
+        progress::{ProgressReporter, default_progress_reporter},
         traits::{ReadSeekSendSync, WriteSeek},
     },
-    mbe::{ColumnType, MBEFile, TableCell},
+    mbe::{ColumnRole, ColumnType, MBEFile, RowKey, SchemaRegistry, TableCell},
     mvgl::{Extractor, Packer},
 };
 
 ///
 /// A structure to handle repack of game's dialogues.
 ///
-pub struct DialogueRepacker<'a> {
-    multi_progress: Option<&'a MultiProgress>,
+pub struct DialogueRepacker {
+    progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    cancellation_token: Option<CancellationToken>,
+    schemas: Option<Arc<SchemaRegistry>>,
 }
 
-impl Default for DialogueRepacker<'_> {
+impl Default for DialogueRepacker {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'a> DialogueRepacker<'a> {
+impl DialogueRepacker {
     pub const fn new() -> Self {
         Self {
-            multi_progress: None,
+            progress_reporter: None,
+            cancellation_token: None,
+            schemas: None,
         }
     }
 
     /// Sets the [`MultiProgress`] to use in the repacking process.
     ///
     /// Useful if you call this function from a script already handling multiple progress bars.
-    pub const fn with_multi_progress(self, multi_progress: Option<&'a MultiProgress>) -> Self {
-        Self { multi_progress }
+    #[cfg(feature = "indicatif")]
+    pub fn with_multi_progress(self, multi_progress: Option<&MultiProgress>) -> Self {
+        self.with_progress_reporter(Some(Arc::new(
+            crate::helpers::progress::IndicatifProgress::new(
+                multi_progress.cloned().unwrap_or_default(),
+            ),
+        )))
+    }
+
+    /// Sets where the progress of the repacking is reported.
+    pub fn with_progress_reporter(
+        self,
+        progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    ) -> Self {
+        Self {
+            progress_reporter,
+            ..self
+        }
+    }
+
+    /// Sets a token that can be used to stop the repacking between two files.
+    ///
+    /// The archive written so far is incomplete: use [`DialogueRepacker::repack_to_path`] to
+    /// make sure it doesn't replace the destination.
+    pub fn with_cancellation_token(self, cancellation_token: Option<CancellationToken>) -> Self {
+        Self {
+            cancellation_token,
+            ..self
+        }
+    }
+
+    /// Sets the schemas telling which columns hold the dialogues to be replaced. Defaults to
+    /// [`SchemaRegistry::default`].
+    pub fn with_schemas(self, schemas: Option<Arc<SchemaRegistry>>) -> Self {
+        Self { schemas, ..self }
+    }
+
+    ///
+    /// Same as [`DialogueRepacker::repack`], but writes the result to `destination_path`
+    /// atomically: the file is only replaced once the new archive is complete.
+    ///
+    pub fn repack_to_path(
+        &self,
+        full_text: &mut dyn Read,
+        reference_mvgl: &mut dyn ReadSeekSendSync,
+        destination_path: &Path,
+    ) -> io::Result<()> {
+        write_atomically(destination_path, |destination| {
+            self.repack(full_text, reference_mvgl, destination)
+        })
     }
 
     ///
@@ -56,17 +115,25 @@ impl<'a> DialogueRepacker<'a> {
         reference_mvgl: &mut dyn ReadSeekSendSync,
         destination: &mut dyn WriteSeek,
     ) -> io::Result<()> {
+        let progress_reporter = self
+            .progress_reporter
+            .clone()
+            .unwrap_or_else(default_progress_reporter);
+        let schemas = self.schemas.clone().unwrap_or_default();
+
         let csv_dir = TempDir::new()?;
         super::separate::separate_csv(Reader::from_reader(full_text), csv_dir.path())?;
 
         let extracted_dir = TempDir::new()?;
         Extractor::new()
-            .with_multi_progress(self.multi_progress)
+            .with_progress_reporter(Some(progress_reporter.clone()))
+            .with_cancellation_token(self.cancellation_token.clone())
             .extract(reference_mvgl, extracted_dir.path())?;
 
         let translation_dir = TempDir::new()?;
         for file in WalkDir::new(extracted_dir.path()) {
             let file = file?;
+            check_cancellation(self.cancellation_token.as_ref())?;
             let file_relative_path = file.path().strip_prefix(extracted_dir.path()).unwrap();
             if file.file_type().is_dir() {
                 let path = translation_dir.path().join(file_relative_path);
@@ -79,53 +146,124 @@ impl<'a> DialogueRepacker<'a> {
                 .with_extension("csv");
             let dest = translation_dir.path().join(file_relative_path);
 
-            let mut source = MBEFile::from_path(file.path()).unwrap();
+            let mut source = MBEFile::from_path(file.path())
+                .map_err(|x| io::Error::other(x.with_path(file_relative_path)))?;
+            // Only the issues brought by the translation are errors, the game's files having
+            // some already
+            let known_issues = source.validate_with_schemas(&schemas);
+            for issue in &known_issues {
+                log::debug!("{}: {issue}", file_relative_path.display());
+            }
 
             if let Ok(reader) = Reader::from_path(&csv_path) {
+                // Sheets without a schema aren't searched, so their dialogues stay untranslated
+                for sheet in
+                    (0..source.number_of_sheets()).flat_map(|x| source.get_sheet_by_index(x))
+                {
+                    if schemas.find(sheet.name(), sheet.column_types()).is_none() {
+                        log::warn!(
+                            "{}: skipping sheet {:?}, which has no known schema",
+                            file_relative_path.display(),
+                            sheet.name()
+                        );
+                    }
+                }
                 for entry in reader.into_byte_records() {
                     let entry = entry?;
-                    let mut rows = source.rows();
-                    if rows.by_ref().any(|x| match x[0] {
-                        TableCell::Int(x) | TableCell::IntID(x) => x == atoi(&entry[0]).unwrap(),
-                        TableCell::StringID(Some(x)) | TableCell::String(Some(x)) => x == &entry[0],
-                        _ => panic!(),
-                    }) && !entry[2].is_empty()
-                    {
-                        let (sheet, row) = if rows.row() == 0 {
-                            let x = source.get_sheet_by_index(rows.sheet() - 1).unwrap();
-                            (rows.sheet() - 1, x.number_of_row().saturating_sub(1))
-                        } else {
-                            (rows.sheet(), rows.row() - 1)
-                        };
-                        let column = if let Some(sheet) = source.get_sheet_by_index(sheet)
-                            && let Some(content) = sheet
-                                .column_types()
-                                .iter()
-                                .position(|&x| x == ColumnType::String)
-                        {
-                            content
-                        } else {
-                            1
-                        };
-                        let res = source.modify_string(sheet, row, column, entry[2].to_vec());
-                        if res.is_none() {
-                            log::info!(
-                                "skipping string {:?}, in file {}, at sheet {sheet}, row {row} and column {column}",
-                                ByteStr::new(&entry[2]),
+                    if entry[2].is_empty() {
+                        continue;
+                    }
+                    let Some((sheet, row, column)) = find_message(&source, &schemas, &entry[0])
+                    else {
+                        log::warn!(
+                            "skipping string {:?}, in file {}: no dialogue has the ID {:?}",
+                            ByteStr::new(&entry[2]),
+                            csv_path.display(),
+                            ByteStr::new(&entry[0])
+                        );
+                        continue;
+                    };
+                    let translated = match entry[2].strip_prefix(NEEDS_REVIEW_MARKER.as_bytes()) {
+                        Some(translated) => {
+                            log::warn!(
+                                "string {:?}, in file {}, still needs review",
+                                ByteStr::new(translated),
                                 csv_path.display()
                             );
+                            translated
                         }
+                        None => &entry[2],
+                    };
+                    let res = source.modify_string(sheet, row, column, translated.to_vec());
+                    if res.is_none() {
+                        log::info!(
+                            "skipping string {:?}, in file {}, at sheet {sheet}, row {row} and column {column}",
+                            ByteStr::new(&entry[2]),
+                            csv_path.display()
+                        );
                     }
                 }
             }
+            let new_issues = source
+                .validate_with_schemas(&schemas)
+                .into_iter()
+                .filter(|x| !known_issues.contains(x))
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>();
+            if !new_issues.is_empty() {
+                return Err(io::Error::other(format!(
+                    "{}: the translated file is invalid: {}",
+                    file_relative_path.display(),
+                    new_issues.join(", ")
+                )));
+            }
             let mut dest_file = BufWriter::new(File::create_new(&dest)?);
             source.write(&mut OffsetWriteWrapper::new(&mut dest_file))?;
         }
 
         Packer::new()
-            .with_multi_progress(self.multi_progress)
+            .with_progress_reporter(Some(progress_reporter.clone()))
+            .with_cancellation_token(self.cancellation_token.clone())
             .pack(translation_dir.path(), destination)?;
 
         Ok(())
     }
 }
+
+///
+/// Finds the dialogue whose ID is `id`, returning its sheet, its row and the column of its
+/// text. Only the sheets described by `schemas` are searched.
+///
+fn find_message(
+    file: &MBEFile,
+    schemas: &SchemaRegistry,
+    id: &[u8],
+) -> Option<(usize, usize, usize)> {
+    (0..file.number_of_sheets()).find_map(|index| {
+        let sheet = file.get_sheet_by_index(index)?;
+        let schema = schemas.find(sheet.name(), sheet.column_types())?;
+        let id_column = schema.column(ColumnRole::MessageId)?;
+        let text_column = schema.column(ColumnRole::Text)?;
+        let key = match sheet.column_types()[id_column] {
+            ColumnType::Int | ColumnType::IntID => RowKey::Int(atoi::<u32>(id)?),
+            ColumnType::String | ColumnType::StringID => RowKey::String(id),
+            _ => return None,
+        };
+        let row = if id_column == 0 {
+            sheet.find_row_by_id(key)?
+        } else {
+            // Only the first column is indexed
+            (0..sheet.number_of_row()).position(|row| {
+                let cell = sheet.get_row(row).and_then(|x| x.get_column(id_column));
+                match (cell, key) {
+                    (Some(TableCell::Int(x) | TableCell::IntID(x)), RowKey::Int(key)) => x == key,
+                    (Some(TableCell::String(x) | TableCell::StringID(x)), RowKey::String(key)) => {
+                        x.map_or(b"".as_slice(), |x| &x.0) == key
+                    }
+                    _ => false,
+                }
+            })?
+        };
+        Some((index, row, text_column))
+    })
+}
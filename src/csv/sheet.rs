@@ -0,0 +1,387 @@
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt::Display,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use byte_string::ByteString;
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+
+use crate::mbe::{
+    ColumnType, EditMBEFileError, MBEFile, RowSelectioner, SchemaRegistry, SheetSchema, TableCell,
+    TableCreateCell,
+};
+
+#[derive(Debug)]
+pub enum ImportCsvError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Edit(EditMBEFileError),
+    /// A header cell doesn't end with the name of a [`ColumnType`].
+    InvalidColumnType {
+        column: usize,
+        name: String,
+    },
+    InvalidValue {
+        row: usize,
+        column: usize,
+        column_type: ColumnType,
+        value: String,
+    },
+    /// A row doesn't have a field for each column.
+    WrongNumberOfFields {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The first row doesn't hold the name of the sheet, as written by
+    /// [`export_sheet_as_csv`].
+    InvalidMetadata(String),
+    /// The file name doesn't start with the index of the sheet.
+    InvalidFileName(PathBuf),
+}
+
+impl Display for ImportCsvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(x) => write!(f, "io error: {x}"),
+            Self::Csv(x) => write!(f, "csv error: {x}"),
+            Self::Edit(x) => write!(f, "{x}"),
+            Self::InvalidColumnType { column, name } => {
+                write!(f, "column {column}: {name:?} isn't a column type")
+            }
+            Self::InvalidValue {
+                row,
+                column,
+                column_type,
+                value,
+            } => write!(
+                f,
+                "row {row}, column {column}: {value:?} isn't a valid {}",
+                column_type.name()
+            ),
+            Self::WrongNumberOfFields {
+                row,
+                expected,
+                found,
+            } => write!(f, "row {row}: expected {expected} fields, found {found}"),
+            Self::InvalidMetadata(x) => write!(f, "invalid metadata row: {x}"),
+            Self::InvalidFileName(x) => write!(
+                f,
+                "{} should be named <sheet index>_<sheet name>.csv",
+                x.display()
+            ),
+        }
+    }
+}
+impl std::error::Error for ImportCsvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(x) => Some(x),
+            Self::Csv(x) => Some(x),
+            Self::Edit(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImportCsvError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<csv::Error> for ImportCsvError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+impl From<EditMBEFileError> for ImportCsvError {
+    fn from(value: EditMBEFileError) -> Self {
+        Self::Edit(value)
+    }
+}
+
+///
+/// Writes a float so that it's parsed back to the same bits. NaNs are written as their
+/// bits in hexadecimal, to keep their payload.
+///
+fn float_to_string(x: f32) -> String {
+    if x.is_nan() {
+        format!("{:#010x}", x.to_bits())
+    } else {
+        x.to_string()
+    }
+}
+
+fn parse_float(value: &str) -> Option<f32> {
+    match value.strip_prefix("0x") {
+        Some(bits) => u32::from_str_radix(bits, 16).ok().map(f32::from_bits),
+        None => value.parse().ok(),
+    }
+}
+
+/// The first field of the row holding the name of the sheet.
+const SHEET_FIELD: &[u8] = b"sheet";
+
+/// Comes before the string fields that would be misread otherwise: empty strings, and
+/// strings starting with it.
+const STRING_ESCAPE: u8 = b'\\';
+
+///
+/// Writes a string cell, telling strings that aren't in the file, written as empty fields,
+/// apart from empty strings. The latter are written as [`STRING_ESCAPE`], which is also
+/// added before strings starting with it.
+///
+fn string_to_field(string: Option<&[u8]>) -> Cow<'_, [u8]> {
+    match string {
+        None => Cow::Borrowed(b""),
+        Some(x) if x.is_empty() || x[0] == STRING_ESCAPE => {
+            Cow::Owned([&[STRING_ESCAPE], x].concat())
+        }
+        Some(x) => Cow::Borrowed(x),
+    }
+}
+
+/// Reads a field written by [`string_to_field`].
+fn parse_string(field: &[u8]) -> Option<&[u8]> {
+    match field {
+        [] => None,
+        [STRING_ESCAPE, rest @ ..] => Some(rest),
+        x => Some(x),
+    }
+}
+
+///
+/// Writes a sheet as CSV.
+///
+/// The first row holds the name of the sheet as is.
+///
+/// The second row holds the name of the [`ColumnType`] of each column, so that the sheet
+/// can be read back with [`import_sheet_from_csv`]. If the sheet has a `schema`, the names
+/// of the columns are written as well, as `<column name>:<column type>`.
+///
+/// Strings that aren't in the file are written as empty fields, and empty strings as a
+/// backslash. Strings starting with a backslash get another one before them.
+///
+/// Cells of unknown types are written as their bytes in hexadecimal, but can't be read
+/// back.
+///
+pub fn export_sheet_as_csv<W: Write>(
+    sheet: RowSelectioner,
+    schema: Option<&SheetSchema>,
+    destination: W,
+) -> csv::Result<()> {
+    let mut destination = WriterBuilder::new().flexible(true).from_writer(destination);
+    destination.write_record([SHEET_FIELD, &sheet.name().0])?;
+    destination.write_record(sheet.column_types().iter().enumerate().map(|(column, x)| {
+        match schema.and_then(|schema| schema.column_name(column)) {
+            Some(name) => format!("{name}:{}", x.name()),
+            None => x.name().to_string(),
+        }
+    }))?;
+    for row in (0..sheet.number_of_row()).flat_map(|x| sheet.get_row(x)) {
+        let mut record = ByteRecord::new();
+        for cell in row.content() {
+            match cell {
+                TableCell::Int(x) | TableCell::IntID(x) => {
+                    record.push_field(x.to_string().as_bytes())
+                }
+                TableCell::Byte(x) => record.push_field(x.to_string().as_bytes()),
+                TableCell::Float(x) => record.push_field(float_to_string(x).as_bytes()),
+                TableCell::String(x) | TableCell::StringID(x) => {
+                    record.push_field(&string_to_field(x.map(|x| &x.0)))
+                }
+                // Written in hexadecimal, for information only
+                TableCell::Unknown(..) => record.push_field(cell.to_string().as_bytes()),
+            }
+        }
+        destination.write_byte_record(&record)?;
+    }
+    destination.flush()?;
+    Ok(())
+}
+
+/// Reads the row written first by [`export_sheet_as_csv`], as the name of the sheet.
+fn parse_metadata(record: Option<ByteRecord>) -> Result<ByteString, ImportCsvError> {
+    let invalid = |x: &[u8]| ImportCsvError::InvalidMetadata(String::from_utf8_lossy(x).into());
+    let record = record.ok_or_else(|| invalid(b"the file is empty"))?;
+    let (Some(SHEET_FIELD), Some(name), None) = (record.get(0), record.get(1), record.get(2))
+    else {
+        return Err(invalid(record.as_slice()));
+    };
+    Ok(ByteString(name.to_vec()))
+}
+
+///
+/// Reads a CSV written by [`export_sheet_as_csv`] and adds it to `file` as a sheet,
+/// returning its index.
+///
+pub fn import_sheet_from_csv<R: Read>(
+    file: &mut MBEFile,
+    source: R,
+) -> Result<usize, ImportCsvError> {
+    let mut records = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(source)
+        .into_byte_records();
+    let name = parse_metadata(records.next().transpose()?)?;
+    let column_types = records
+        .next()
+        .transpose()?
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(column, name)| {
+            // The name of the column is only informative
+            let name = String::from_utf8_lossy(name);
+            let type_ = name.rsplit_once(':').map_or(&*name, |(_, type_)| type_);
+            ColumnType::from_name(type_).ok_or_else(|| ImportCsvError::InvalidColumnType {
+                column,
+                name: name.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let records = records.collect::<Result<Vec<_>, _>>()?;
+
+    // Every row is checked before the sheet is added, so that `file` is left untouched on
+    // errors
+    let mut empty_strings = Vec::new();
+    let rows = records
+        .iter()
+        .enumerate()
+        .map(|(row, record)| {
+            if record.len() != column_types.len() {
+                return Err(ImportCsvError::WrongNumberOfFields {
+                    row,
+                    expected: column_types.len(),
+                    found: record.len(),
+                });
+            }
+            let empty_strings = &mut empty_strings;
+            record
+                .iter()
+                .zip(&column_types)
+                .enumerate()
+                .map(move |(column, (value, &column_type))| {
+                    let invalid_value = || ImportCsvError::InvalidValue {
+                        row,
+                        column,
+                        column_type,
+                        value: String::from_utf8_lossy(value).into_owned(),
+                    };
+                    let number = || {
+                        std::str::from_utf8(value)
+                            .map_err(|_| invalid_value())
+                            .map(str::trim)
+                    };
+                    Ok(match column_type {
+                        ColumnType::Int => {
+                            TableCreateCell::Int(number()?.parse().map_err(|_| invalid_value())?)
+                        }
+                        ColumnType::IntID => {
+                            TableCreateCell::IntID(number()?.parse().map_err(|_| invalid_value())?)
+                        }
+                        ColumnType::Byte => {
+                            TableCreateCell::Byte(number()?.parse().map_err(|_| invalid_value())?)
+                        }
+                        ColumnType::Float => TableCreateCell::Float(
+                            parse_float(number()?).ok_or_else(invalid_value)?,
+                        ),
+                        ColumnType::String | ColumnType::StringID => {
+                            let string = parse_string(value);
+                            if string.is_some_and(<[u8]>::is_empty) {
+                                empty_strings.push((row, column));
+                            }
+                            let string = Cow::Borrowed(string.unwrap_or_default());
+                            match column_type {
+                                ColumnType::String => TableCreateCell::String(string),
+                                _ => TableCreateCell::StringID(string),
+                            }
+                        }
+                        ColumnType::Unknown(_) => {
+                            return Err(EditMBEFileError::UnknownColumnType(column_type).into());
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, ImportCsvError>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let sheet = file.add_sheet(name, column_types)?;
+    for (row, cells) in rows.into_iter().enumerate() {
+        file.insert_row(sheet, row, cells)?;
+    }
+    for (row, column) in empty_strings {
+        file.set_empty_string(sheet, row, column)?;
+    }
+    Ok(sheet)
+}
+
+///
+/// Writes every sheet of `file` in `destination`, as `<sheet index>_<sheet name>.csv`.
+/// Columns are named after the schemas of `schemas`.
+///
+/// The name in the file name is only informative, the characters that can't be in a path
+/// being replaced by `_`. The actual name is read back from the file.
+///
+pub fn export_as_csv_directory(
+    file: &MBEFile,
+    schemas: &SchemaRegistry,
+    destination: &Path,
+) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    let width = file.number_of_sheets().to_string().len();
+    for index in 0..file.number_of_sheets() {
+        let sheet = file.get_sheet_by_index(index).unwrap();
+        let name: String = String::from_utf8_lossy(sheet.name())
+            .chars()
+            .map(|x| {
+                if x.is_alphanumeric() || "-_. ".contains(x) {
+                    x
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = destination.join(format!("{index:0width$}_{name}.csv"));
+        let schema = schemas.find(sheet.name(), sheet.column_types());
+        export_sheet_as_csv(sheet, schema, fs::File::create(path)?)?;
+    }
+    Ok(())
+}
+
+///
+/// Reads a directory written by [`export_as_csv_directory`] back into an [`MBEFile`].
+/// Sheets are ordered by their index.
+///
+pub fn import_from_csv_directory(source: &Path) -> Result<MBEFile, ImportCsvError> {
+    let mut sheets = Vec::new();
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("csv")) {
+            continue;
+        }
+        let Some(index) = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .and_then(|x| x.split_once('_'))
+            .and_then(|(index, _)| index.parse::<usize>().ok())
+        else {
+            return Err(ImportCsvError::InvalidFileName(path));
+        };
+        sheets.push((index, path));
+    }
+    sheets.sort_unstable_by_key(|&(index, _)| index);
+
+    let mut file = MBEFile::new();
+    for (_, path) in sheets {
+        import_sheet_from_csv(&mut file, fs::File::open(path)?)?;
+    }
+    Ok(file)
+}
@@ -1,3 +1,8 @@
+pub(crate) mod atomic_file;
+pub(crate) mod binary;
+pub mod cancellation;
+#[cfg(feature = "indicatif")]
 pub(crate) mod indicatif;
 pub mod offset_wrapper;
+pub mod progress;
 pub(crate) mod traits;
@@ -0,0 +1,48 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+///
+/// Calls `write` on a temporary file next to `destination`, then moves it to `destination`.
+///
+/// The temporary file is synced to disk before being renamed, so `destination` either keeps
+/// its previous content or gets the complete new one, even if `write` fails or the process
+/// is interrupted. On failure, the temporary file is removed. An existing `destination`
+/// keeps its permissions.
+///
+pub fn write_atomically<T>(
+    destination: &Path,
+    write: impl FnOnce(&mut BufWriter<&File>) -> io::Result<T>,
+) -> io::Result<T> {
+    let parent = match destination.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    // Like a file created with `File::create`, instead of only being readable by its owner
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let temp_file = builder.tempfile_in(parent)?;
+
+    let mut writer = BufWriter::new(temp_file.as_file());
+    let result = write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    // The target keeps its permissions
+    if let Ok(metadata) = fs::metadata(destination) {
+        temp_file
+            .as_file()
+            .set_permissions(metadata.permissions())?;
+    }
+    temp_file.as_file().sync_all()?;
+    temp_file.persist(destination).map_err(|e| e.error)?;
+
+    // The rename itself is only durable once the directory entry is written.
+    #[cfg(unix)]
+    File::open(parent)?.sync_all()?;
+
+    Ok(result)
+}
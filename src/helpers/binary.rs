@@ -0,0 +1,200 @@
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+///
+/// A part of a file that is read and written the same way, so that the parser and the
+/// writer can't disagree on it.
+///
+/// Structures are usually declared with [`binary_layout!`], which implements this trait
+/// from the list of their fields.
+///
+pub(crate) trait BinaryLayout: Sized {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self>;
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()>;
+}
+
+impl BinaryLayout for u32 {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self> {
+        source.read_u32::<LittleEndian>()
+    }
+
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()> {
+        destination.write_u32::<LittleEndian>(*self)
+    }
+}
+
+impl BinaryLayout for u64 {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self> {
+        source.read_u64::<LittleEndian>()
+    }
+
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()> {
+        destination.write_u64::<LittleEndian>(*self)
+    }
+}
+
+impl<const N: usize> BinaryLayout for [u8; N] {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self> {
+        let mut bytes = [0; N];
+        source.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()> {
+        destination.write_all(self)
+    }
+}
+
+/// A list preceded by its length, as a `u32`.
+impl<T: BinaryLayout> BinaryLayout for Vec<T> {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self> {
+        let length = u32::read_from(source)?;
+        (0..length).map(|_| T::read_from(source)).collect()
+    }
+
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()> {
+        (self.len() as u32).write_to(destination)?;
+        self.iter().try_for_each(|x| x.write_to(destination))
+    }
+}
+
+///
+/// The error, wrapped in an [`io::Error`] of kind [`io::ErrorKind::InvalidData`], when the
+/// bytes that must be at some place, like a magic number, aren't there.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnexpectedContents {
+    pub expected: Vec<u8>,
+    pub found: Vec<u8>,
+}
+
+impl UnexpectedContents {
+    /// The bytes `error` was expecting, if it's an [`UnexpectedContents`].
+    pub fn expected_by(error: &io::Error) -> Option<&[u8]> {
+        error
+            .get_ref()
+            .and_then(|x| x.downcast_ref::<Self>())
+            .map(|x| &*x.expected)
+    }
+}
+
+impl Display for UnexpectedContents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected {:?}, found {:?}",
+            byte_string::ByteStr::new(&self.expected),
+            byte_string::ByteStr::new(&self.found)
+        )
+    }
+}
+impl std::error::Error for UnexpectedContents {}
+
+/// Reads the bytes that must be at this place, failing if they aren't `expected`.
+pub(crate) fn read_contents(source: &mut dyn Read, expected: &[u8]) -> io::Result<()> {
+    let mut found = vec![0; expected.len()];
+    source.read_exact(&mut found)?;
+    if found != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            UnexpectedContents {
+                expected: expected.to_vec(),
+                found,
+            },
+        ));
+    }
+    Ok(())
+}
+
+///
+/// Declares a structure and implements [`BinaryLayout`] for it, its fields being read and
+/// written one after the other, in order. Every entry ends with a comma, and is either:
+///
+/// - a field, whose type implements [`BinaryLayout`],
+/// - `const <bytes>`, bytes that must be there but aren't kept, like a magic number.
+///
+/// ```ignore
+/// binary_layout! {
+///     struct Chunk {
+///         const b"CHNK",
+///         count: u32,
+///     }
+/// }
+/// ```
+///
+macro_rules! binary_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($body:tt)*
+        }
+    ) => {
+        $crate::helpers::binary::binary_layout!(
+            @munch [$(#[$meta])* $vis struct $name] [source destination] [] [] [] []
+            $($body)*
+        );
+    };
+    (
+        @munch $head:tt [$source:ident $destination:ident]
+        [$($fields:tt)*] [$($names:ident)*] [$($reads:tt)*] [$($writes:tt)*]
+        $(#[$contents_meta:meta])* const $contents:expr, $($rest:tt)*
+    ) => {
+        $crate::helpers::binary::binary_layout!(
+            @munch $head [$source $destination] [$($fields)*] [$($names)*]
+            [
+                $($reads)*
+                $crate::helpers::binary::read_contents($source, &$contents[..])?;
+            ]
+            [$($writes)* std::io::Write::write_all($destination, &$contents[..])?;]
+            $($rest)*
+        );
+    };
+    (
+        @munch $head:tt [$source:ident $destination:ident]
+        [$($fields:tt)*] [$($names:ident)*] [$($reads:tt)*] [$($writes:tt)*]
+        $(#[$field_meta:meta])* $field_vis:vis $field:ident : $type:ty, $($rest:tt)*
+    ) => {
+        $crate::helpers::binary::binary_layout!(
+            @munch $head [$source $destination]
+            [$($fields)* $(#[$field_meta])* $field_vis $field: $type,]
+            [$($names)* $field]
+            [
+                $($reads)*
+                let $field =
+                    <$type as $crate::helpers::binary::BinaryLayout>::read_from($source)?;
+            ]
+            [
+                $($writes)*
+                $crate::helpers::binary::BinaryLayout::write_to($field, $destination)?;
+            ]
+            $($rest)*
+        );
+    };
+    (
+        @munch [$(#[$meta:meta])* $vis:vis struct $name:ident] [$source:ident $destination:ident]
+        [$($fields:tt)*] [$($names:ident)*] [$($reads:tt)*] [$($writes:tt)*]
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($fields)*
+        }
+
+        impl $crate::helpers::binary::BinaryLayout for $name {
+            fn read_from($source: &mut dyn std::io::Read) -> std::io::Result<Self> {
+                $($reads)*
+                Ok(Self { $($names),* })
+            }
+
+            fn write_to(&self, $destination: &mut dyn std::io::Write) -> std::io::Result<()> {
+                let Self { $($names),* } = self;
+                $($writes)*
+                Ok(())
+            }
+        }
+    };
+}
+pub(crate) use binary_layout;
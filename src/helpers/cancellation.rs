@@ -0,0 +1,78 @@
+use std::{
+    fmt::Display,
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+///
+/// A flag shared between a long-running operation and whoever may want to stop it.
+///
+/// Cloning the token gives another handle to the same flag. Operations check it between
+/// entries, and stop with a [`Cancelled`] error once [`CancellationToken::cancel`] is called.
+///
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(value: Arc<AtomicBool>) -> Self {
+        Self(value)
+    }
+}
+
+///
+/// The error returned by an operation stopped through its [`CancellationToken`].
+///
+/// It's wrapped in an [`io::Error`] of kind [`io::ErrorKind::Other`], and not
+/// [`io::ErrorKind::Interrupted`], which readers and writers retry; use [`Cancelled::is`] to
+/// tell it apart from other errors.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Cancelled {
+    pub fn is(error: &io::Error) -> bool {
+        error
+            .get_ref()
+            .is_some_and(|inner| inner.downcast_ref::<Self>().is_some())
+    }
+}
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl From<Cancelled> for io::Error {
+    fn from(value: Cancelled) -> Self {
+        io::Error::other(value)
+    }
+}
+
+/// Returns a [`Cancelled`] error if `token` was cancelled.
+pub(crate) fn check_cancellation(token: Option<&CancellationToken>) -> io::Result<()> {
+    if token.is_some_and(CancellationToken::is_cancelled) {
+        Err(Cancelled.into())
+    } else {
+        Ok(())
+    }
+}
@@ -2,16 +2,6 @@ use indicatif::MultiProgress;
 
 pub trait IndicatifProgressExt {
     fn in_multi_progress(self, multi_progress: &MultiProgress) -> Self;
-    fn in_optional_multi_progress(self, multi_progress: Option<&MultiProgress>) -> Self
-    where
-        Self: Sized,
-    {
-        if let Some(multi_progress) = multi_progress {
-            self.in_multi_progress(multi_progress)
-        } else {
-            self
-        }
-    }
 }
 
 impl<T> IndicatifProgressExt for indicatif::ProgressBarIter<T> {
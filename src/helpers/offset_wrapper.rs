@@ -15,13 +15,22 @@ impl<'a> OffsetReadWrapper<'a> {
     }
 
     pub fn align(&mut self, alignment: u64) -> std::io::Result<u64> {
-        let alignment = self.offset() as u64 % alignment;
-        if alignment > 0 {
-            std::io::copy(&mut self.take(alignment), &mut std::io::sink())
+        let padding = (self.offset() as u64).next_multiple_of(alignment) - self.offset() as u64;
+        if padding > 0 {
+            std::io::copy(&mut self.take(padding), &mut std::io::sink())
         } else {
             Ok(0)
         }
     }
+
+    /// Like [`Self::align`], but returns the skipped bytes. There may be less of them if
+    /// the end of the source is reached.
+    pub fn read_alignment(&mut self, alignment: u64) -> std::io::Result<Vec<u8>> {
+        let padding = (self.offset() as u64).next_multiple_of(alignment) - self.offset() as u64;
+        let mut skipped = Vec::with_capacity(padding as usize);
+        self.take(padding).read_to_end(&mut skipped)?;
+        Ok(skipped)
+    }
 }
 
 impl io::Read for OffsetReadWrapper<'_> {
@@ -47,9 +56,9 @@ impl<'a> OffsetWriteWrapper<'a> {
     }
 
     pub fn align(&mut self, alignment: u64, byte: u8) -> io::Result<u64> {
-        let alignment = self.offset() as u64 % alignment;
-        if alignment > 0 {
-            std::io::copy(&mut std::io::repeat(byte).take(alignment), self)
+        let padding = (self.offset() as u64).next_multiple_of(alignment) - self.offset() as u64;
+        if padding > 0 {
+            std::io::copy(&mut std::io::repeat(byte).take(padding), self)
         } else {
             Ok(0)
         }
@@ -0,0 +1,277 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    mpsc::{Receiver, Sender, channel},
+};
+
+///
+/// What the position and total of a phase count.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressUnit {
+    Items,
+    Bytes,
+}
+
+///
+/// Receives the progress of long-running operations.
+///
+/// An operation is made of phases (for example "collecting files" then "compressing
+/// file"), each started with [`ProgressReporter::start_phase`]. Phases may overlap, for
+/// example when an operation calls another one.
+///
+pub trait ProgressReporter: Send + Sync {
+    /// Starts a new phase. `total` is `None` when the amount of work isn't known yet.
+    fn start_phase(
+        &self,
+        name: &str,
+        total: Option<u64>,
+        unit: ProgressUnit,
+    ) -> Box<dyn ProgressPhase>;
+}
+
+///
+/// The progress of a single phase, as returned by [`ProgressReporter::start_phase`].
+///
+/// A phase dropped without being finished, for example when the operation fails, is
+/// abandoned.
+///
+pub trait ProgressPhase: Send + Sync {
+    fn set_total(&self, total: u64);
+    fn advance(&self, delta: u64);
+    /// Describes what's currently being worked on.
+    fn set_message(&self, message: &str);
+    fn finish(&self, message: &str);
+}
+
+///
+/// A [`ProgressReporter`] ignoring everything.
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn start_phase(&self, _: &str, _: Option<u64>, _: ProgressUnit) -> Box<dyn ProgressPhase> {
+        Box::new(NoProgress)
+    }
+}
+
+impl ProgressPhase for NoProgress {
+    fn set_total(&self, _: u64) {}
+    fn advance(&self, _: u64) {}
+    fn set_message(&self, _: &str) {}
+    fn finish(&self, _: &str) {}
+}
+
+///
+/// An event sent by [`ChannelProgress`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressEvent {
+    /// Identifies the phase this event belongs to, unique per reporter.
+    pub phase: usize,
+    pub kind: ProgressEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEventKind {
+    Started {
+        name: String,
+        total: Option<u64>,
+        unit: ProgressUnit,
+    },
+    TotalSet(u64),
+    Advanced(u64),
+    Message(String),
+    Finished(String),
+    /// The phase was dropped without being finished.
+    Abandoned,
+}
+
+///
+/// A [`ProgressReporter`] sending every update as a [`ProgressEvent`] through a channel.
+///
+/// Useful to observe progress from another thread, for example in a GUI. Events are
+/// dropped once the receiver is gone.
+///
+#[derive(Debug, Clone)]
+pub struct ChannelProgress {
+    sender: Sender<ProgressEvent>,
+    next_phase: Arc<AtomicUsize>,
+}
+
+impl ChannelProgress {
+    pub fn new() -> (Self, Receiver<ProgressEvent>) {
+        let (sender, receiver) = channel();
+        (
+            Self {
+                sender,
+                next_phase: Arc::default(),
+            },
+            receiver,
+        )
+    }
+}
+
+impl ProgressReporter for ChannelProgress {
+    fn start_phase(
+        &self,
+        name: &str,
+        total: Option<u64>,
+        unit: ProgressUnit,
+    ) -> Box<dyn ProgressPhase> {
+        let phase = ChannelPhase {
+            sender: self.sender.clone(),
+            phase: self.next_phase.fetch_add(1, Ordering::Relaxed),
+            finished: AtomicBool::new(false),
+        };
+        phase.send(ProgressEventKind::Started {
+            name: name.to_string(),
+            total,
+            unit,
+        });
+        Box::new(phase)
+    }
+}
+
+struct ChannelPhase {
+    sender: Sender<ProgressEvent>,
+    phase: usize,
+    finished: AtomicBool,
+}
+
+impl ChannelPhase {
+    fn send(&self, kind: ProgressEventKind) {
+        let _ = self.sender.send(ProgressEvent {
+            phase: self.phase,
+            kind,
+        });
+    }
+}
+
+impl ProgressPhase for ChannelPhase {
+    fn set_total(&self, total: u64) {
+        self.send(ProgressEventKind::TotalSet(total));
+    }
+
+    fn advance(&self, delta: u64) {
+        self.send(ProgressEventKind::Advanced(delta));
+    }
+
+    fn set_message(&self, message: &str) {
+        self.send(ProgressEventKind::Message(message.to_string()));
+    }
+
+    fn finish(&self, message: &str) {
+        self.finished.store(true, Ordering::Relaxed);
+        self.send(ProgressEventKind::Finished(message.to_string()));
+    }
+}
+
+impl Drop for ChannelPhase {
+    fn drop(&mut self) {
+        if !*self.finished.get_mut() {
+            self.send(ProgressEventKind::Abandoned);
+        }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+pub use indicatif_progress::IndicatifProgress;
+
+#[cfg(feature = "indicatif")]
+mod indicatif_progress {
+    use std::time::Duration;
+
+    use indicatif::{MultiProgress, ProgressBar};
+
+    use crate::helpers::indicatif::{
+        IndicatifProgressExt, byte_bar_style_with_message_header,
+        default_bar_style_with_message_header, default_spinner_style_with_message_header,
+    };
+
+    use super::{ProgressPhase, ProgressReporter, ProgressUnit};
+
+    ///
+    /// A [`ProgressReporter`] drawing each phase as an indicatif progress bar.
+    ///
+    /// All bars are added to the same [`MultiProgress`], so that overlapping phases are
+    /// drawn below each other.
+    ///
+    #[derive(Debug, Clone, Default)]
+    pub struct IndicatifProgress {
+        multi_progress: MultiProgress,
+    }
+
+    impl IndicatifProgress {
+        pub fn new(multi_progress: MultiProgress) -> Self {
+            Self { multi_progress }
+        }
+    }
+
+    impl ProgressReporter for IndicatifProgress {
+        fn start_phase(
+            &self,
+            name: &str,
+            total: Option<u64>,
+            unit: ProgressUnit,
+        ) -> Box<dyn ProgressPhase> {
+            let progress_bar = match (total, unit) {
+                (None, _) => {
+                    let spinner = ProgressBar::new_spinner()
+                        .with_style(default_spinner_style_with_message_header(name));
+                    spinner.enable_steady_tick(Duration::from_millis(200));
+                    spinner
+                }
+                (Some(total), ProgressUnit::Bytes) => {
+                    ProgressBar::new(total).with_style(byte_bar_style_with_message_header(name))
+                }
+                (Some(total), ProgressUnit::Items) => {
+                    ProgressBar::new(total).with_style(default_bar_style_with_message_header(name))
+                }
+            };
+            Box::new(IndicatifPhase(
+                progress_bar.in_multi_progress(&self.multi_progress),
+            ))
+        }
+    }
+
+    struct IndicatifPhase(ProgressBar);
+
+    impl ProgressPhase for IndicatifPhase {
+        fn set_total(&self, total: u64) {
+            self.0.set_length(total);
+        }
+
+        fn advance(&self, delta: u64) {
+            self.0.inc(delta);
+        }
+
+        fn set_message(&self, message: &str) {
+            self.0.set_message(message.to_string());
+        }
+
+        fn finish(&self, message: &str) {
+            self.0.finish_with_message(message.to_string());
+        }
+    }
+
+    impl Drop for IndicatifPhase {
+        fn drop(&mut self) {
+            // Stops the spinners from ticking, and leaves the bar as it was
+            if !self.0.is_finished() {
+                self.0.abandon();
+            }
+        }
+    }
+}
+
+///
+/// The reporter used by builders that weren't given one.
+///
+pub(crate) fn default_progress_reporter() -> Arc<dyn ProgressReporter> {
+    #[cfg(feature = "indicatif")]
+    return Arc::new(IndicatifProgress::default());
+    #[cfg(not(feature = "indicatif"))]
+    return Arc::new(NoProgress);
+}
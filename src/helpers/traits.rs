@@ -8,3 +8,6 @@ impl<T: Read + Seek> ReadSeek for T {}
 
 pub trait ReadSeekSendSync: Read + Seek + Send + Sync {}
 impl<T: Read + Seek + Send + Sync> ReadSeekSendSync for T {}
+
+pub trait ReadWriteSeekSend: Read + Write + Seek + Send {}
+impl<T: Read + Write + Seek + Send> ReadWriteSeekSend for T {}
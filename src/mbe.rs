@@ -1,6 +1,17 @@
+mod diff;
 mod file;
+mod row;
+mod schema;
 
+pub use diff::{CellDiff, MBEDiff, RowChange, RowDiff, SheetChange, SheetDiff, diff};
 pub use file::{
-    ColumnSelectioner, ColumnType, MBEFile, ParseMBEFileError, PublicTableCell as TableCell,
-    RowSelectioner, Sheet,
+    ColumnSelectioner, ColumnType, EditMBEFileError, FieldLayout, MBEFile, MBEView,
+    ParseMBEFileError, ParseMBEFileErrorKind, PatchMBEFileError, PublicTableCell as TableCell,
+    RowIterator, RowKey, RowRef, RowSelectioner, RowView, Sheet, SheetIterator, SheetRowIterator,
+    SheetView, TableCreateCell, TableFormat, TablePrinter, ValidationIssue, VerifyRoundtripError,
+    verify_roundtrip,
 };
+pub use row::{__private, FromMbeCell, FromMbeRow, RowMappingError, ToMbeCell, ToMbeRow};
+pub use schema::{ColumnRole, ColumnSchema, SchemaError, SchemaRegistry, SheetSchema};
+#[cfg(feature = "derive")]
+pub use thl_tools_derive::{FromMbeRow, ToMbeRow};
@@ -0,0 +1,427 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
+
+use super::{ColumnType, MBEFile, RowSelectioner, TableCell};
+
+///
+/// The changes between two MBE files, made by [`diff`].
+///
+/// It's written as text by its [`Display`] implementation, and as JSON by
+/// [`MBEDiff::to_json`] if the `serde` feature is enabled.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MBEDiff<'a> {
+    sheets: Vec<SheetDiff<'a>>,
+}
+
+///
+/// The changes of a sheet. Added and removed sheets list all of their rows as added or
+/// removed.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SheetDiff<'a> {
+    name: String,
+    change: SheetChange,
+    rows: Vec<RowDiff<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SheetChange {
+    Added,
+    Removed,
+    Modified,
+    /// The columns changed, so the rows can't be compared.
+    ColumnTypesChanged {
+        old: Vec<ColumnType>,
+        new: Vec<ColumnType>,
+    },
+}
+
+///
+/// A row that was added, removed or modified. Rows are matched by the value of their first
+/// column, or by position if it can't be an ID.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RowDiff<'a> {
+    /// The value of the first column, if the row was matched by ID.
+    id: Option<TableCell<'a>>,
+    /// The position of the row in the old sheet, unless it was added.
+    old_row: Option<usize>,
+    /// The position of the row in the new sheet, unless it was removed.
+    new_row: Option<usize>,
+    change: RowChange<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RowChange<'a> {
+    Added(Vec<TableCell<'a>>),
+    Removed(Vec<TableCell<'a>>),
+    Modified(Vec<CellDiff<'a>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CellDiff<'a> {
+    column: usize,
+    before: TableCell<'a>,
+    after: TableCell<'a>,
+}
+
+impl<'a> MBEDiff<'a> {
+    /// The sheets that changed, in the order of the new file, then the removed ones.
+    pub fn sheets(&self) -> &[SheetDiff<'a>] {
+        &self.sheets
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sheets.is_empty()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl<'a> SheetDiff<'a> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn change(&self) -> &SheetChange {
+        &self.change
+    }
+
+    ///
+    /// The rows that changed: the added and modified rows in the order of the new sheet,
+    /// then the removed rows in the order of the old sheet.
+    ///
+    pub fn rows(&self) -> &[RowDiff<'a>] {
+        &self.rows
+    }
+}
+
+impl<'a> RowDiff<'a> {
+    pub fn id(&self) -> Option<TableCell<'a>> {
+        self.id
+    }
+
+    pub fn old_row(&self) -> Option<usize> {
+        self.old_row
+    }
+
+    pub fn new_row(&self) -> Option<usize> {
+        self.new_row
+    }
+
+    pub fn change(&self) -> &RowChange<'a> {
+        &self.change
+    }
+}
+
+impl<'a> CellDiff<'a> {
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn before(&self) -> TableCell<'a> {
+        self.before
+    }
+
+    pub fn after(&self) -> TableCell<'a> {
+        self.after
+    }
+}
+
+/// The key rows are matched with. Strings without content match empty strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum DiffKey<'a> {
+    Int(u32),
+    String(&'a [u8]),
+    /// The position of a row whose first column can't be an ID.
+    Position(usize),
+}
+
+impl<'a> DiffKey<'a> {
+    fn of(row: &[TableCell<'a>], position: usize) -> Self {
+        match row.first() {
+            Some(TableCell::Int(x) | TableCell::IntID(x)) => Self::Int(*x),
+            Some(TableCell::String(x) | TableCell::StringID(x)) => {
+                Self::String(x.map_or(b"".as_slice(), |x| &x.0))
+            }
+            _ => Self::Position(position),
+        }
+    }
+}
+
+/// The ID of a row, if its first column can be one.
+fn id<'a>(row: &[TableCell<'a>]) -> Option<TableCell<'a>> {
+    row.first().copied().filter(|x| {
+        matches!(
+            x,
+            TableCell::Int(_) | TableCell::IntID(_) | TableCell::String(_) | TableCell::StringID(_)
+        )
+    })
+}
+
+/// Whether two cells hold the same value, floats being compared by their bits.
+fn same_cell(a: TableCell, b: TableCell) -> bool {
+    match (a, b) {
+        (TableCell::Float(a), TableCell::Float(b)) => a.to_bits() == b.to_bits(),
+        _ => a == b,
+    }
+}
+
+fn rows<'a>(sheet: RowSelectioner<'a>) -> Vec<Vec<TableCell<'a>>> {
+    (0..sheet.number_of_row())
+        .flat_map(|x| sheet.get_row(x))
+        .map(|x| x.content())
+        .collect()
+}
+
+fn diff_rows<'a>(old: RowSelectioner<'a>, new: RowSelectioner<'a>) -> Vec<RowDiff<'a>> {
+    let old_rows = rows(old);
+    let mut positions = HashMap::<_, VecDeque<_>>::new();
+    for (position, row) in old_rows.iter().enumerate() {
+        positions
+            .entry(DiffKey::of(row, position))
+            .or_default()
+            .push_back(position);
+    }
+
+    let mut matched = vec![false; old_rows.len()];
+    let mut diffs = Vec::new();
+    for (new_row, new_cells) in rows(new).into_iter().enumerate() {
+        // Rows sharing an ID are matched in order
+        let Some(old_row) = positions
+            .get_mut(&DiffKey::of(&new_cells, new_row))
+            .and_then(VecDeque::pop_front)
+        else {
+            diffs.push(RowDiff {
+                id: id(&new_cells),
+                old_row: None,
+                new_row: Some(new_row),
+                change: RowChange::Added(new_cells),
+            });
+            continue;
+        };
+        matched[old_row] = true;
+        let cells = old_rows[old_row]
+            .iter()
+            .zip(new_cells)
+            .enumerate()
+            .filter(|&(_, (&before, after))| !same_cell(before, after))
+            .map(|(column, (&before, after))| CellDiff {
+                column,
+                before,
+                after,
+            })
+            .collect::<Vec<_>>();
+        if !cells.is_empty() {
+            diffs.push(RowDiff {
+                id: id(&old_rows[old_row]),
+                old_row: Some(old_row),
+                new_row: Some(new_row),
+                change: RowChange::Modified(cells),
+            });
+        }
+    }
+
+    diffs.extend(
+        old_rows
+            .into_iter()
+            .enumerate()
+            .filter(|&(position, _)| !matched[position])
+            .map(|(old_row, cells)| RowDiff {
+                id: id(&cells),
+                old_row: Some(old_row),
+                new_row: None,
+                change: RowChange::Removed(cells),
+            }),
+    );
+    diffs
+}
+
+///
+/// Compares two MBE files.
+///
+/// Sheets are matched by name, and rows by the value of their first column, like with
+/// [`MBEFile::patch`]. Rows sharing an ID are matched in order, and rows of sheets whose
+/// first column can't be an ID are matched by position. Only the sheets and the rows that
+/// changed are reported.
+///
+pub fn diff<'a>(old: &'a MBEFile, new: &'a MBEFile) -> MBEDiff<'a> {
+    let old_sheets = (0..old.number_of_sheets())
+        .flat_map(|x| old.get_sheet_by_index(x))
+        .collect::<Vec<_>>();
+    let mut sheets = Vec::new();
+
+    for new_sheet in (0..new.number_of_sheets()).flat_map(|x| new.get_sheet_by_index(x)) {
+        let name = String::from_utf8_lossy(new_sheet.name()).into_owned();
+        let Some(old_sheet) = old_sheets.iter().find(|x| x.name() == new_sheet.name()) else {
+            let rows = rows(new_sheet)
+                .into_iter()
+                .enumerate()
+                .map(|(new_row, cells)| RowDiff {
+                    id: id(&cells),
+                    old_row: None,
+                    new_row: Some(new_row),
+                    change: RowChange::Added(cells),
+                })
+                .collect();
+            sheets.push(SheetDiff {
+                name,
+                change: SheetChange::Added,
+                rows,
+            });
+            continue;
+        };
+
+        if old_sheet.column_types() != new_sheet.column_types() {
+            sheets.push(SheetDiff {
+                name,
+                change: SheetChange::ColumnTypesChanged {
+                    old: old_sheet.column_types().to_vec(),
+                    new: new_sheet.column_types().to_vec(),
+                },
+                rows: Vec::new(),
+            });
+            continue;
+        }
+
+        let rows = diff_rows(*old_sheet, new_sheet);
+        if !rows.is_empty() {
+            sheets.push(SheetDiff {
+                name,
+                change: SheetChange::Modified,
+                rows,
+            });
+        }
+    }
+
+    for old_sheet in old_sheets
+        .into_iter()
+        .filter(|x| new.get_sheet_by_name(x.name()).is_none())
+    {
+        let rows = rows(old_sheet)
+            .into_iter()
+            .enumerate()
+            .map(|(old_row, cells)| RowDiff {
+                id: id(&cells),
+                old_row: Some(old_row),
+                new_row: None,
+                change: RowChange::Removed(cells),
+            })
+            .collect();
+        sheets.push(SheetDiff {
+            name: String::from_utf8_lossy(old_sheet.name()).into_owned(),
+            change: SheetChange::Removed,
+            rows,
+        });
+    }
+
+    MBEDiff { sheets }
+}
+
+/// Writes cells separated by `|`, with strings quoted.
+struct DisplayCells<'c, 'a>(&'c [TableCell<'a>]);
+
+impl Display for DisplayCells<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, cell) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{}", DisplayCell(*cell))?;
+        }
+        Ok(())
+    }
+}
+
+struct DisplayCell<'a>(TableCell<'a>);
+
+impl Display for DisplayCell<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            TableCell::String(_) | TableCell::StringID(_) => write!(f, "{:?}", self.0.to_string()),
+            cell => write!(f, "{cell}"),
+        }
+    }
+}
+
+///
+/// Writes the diff as text, one line per row and per modified cell:
+///
+/// ```text
+/// sheet "message" (modified)
+///   + row 3: 12 | 1 | "New line"
+///   - row 5: 14 | 2 | "Removed line"
+///   ~ row 7 (row 6 before), ID 15:
+///       column 2: "Before" -> "After"
+/// ```
+///
+impl Display for MBEDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for sheet in &self.sheets {
+            write!(f, "sheet {:?} ", sheet.name)?;
+            match &sheet.change {
+                SheetChange::Added => writeln!(f, "(added)")?,
+                SheetChange::Removed => writeln!(f, "(removed)")?,
+                SheetChange::Modified => writeln!(f, "(modified)")?,
+                SheetChange::ColumnTypesChanged { old, new } => {
+                    let names = |types: &[ColumnType]| {
+                        types
+                            .iter()
+                            .map(|x| x.name())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    };
+                    writeln!(
+                        f,
+                        "(column types changed from [{}] to [{}])",
+                        names(old),
+                        names(new)
+                    )?
+                }
+            }
+            for row in &sheet.rows {
+                match (&row.change, row.old_row, row.new_row) {
+                    (RowChange::Added(cells), _, Some(new_row)) => {
+                        writeln!(f, "  + row {new_row}: {}", DisplayCells(cells))?
+                    }
+                    (RowChange::Removed(cells), Some(old_row), _) => {
+                        writeln!(f, "  - row {old_row}: {}", DisplayCells(cells))?
+                    }
+                    (RowChange::Modified(cells), Some(old_row), Some(new_row)) => {
+                        write!(f, "  ~ row {new_row}")?;
+                        if old_row != new_row {
+                            write!(f, " (row {old_row} before)")?;
+                        }
+                        match row.id {
+                            Some(id) => writeln!(f, ", ID {}:", DisplayCell(id))?,
+                            None => writeln!(f, ":")?,
+                        }
+                        for cell in cells {
+                            writeln!(
+                                f,
+                                "      column {}: {} -> {}",
+                                cell.column,
+                                DisplayCell(cell.before),
+                                DisplayCell(cell.after)
+                            )?;
+                        }
+                    }
+                    _ => unreachable!("rows are added, removed or modified"),
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,61 +1,160 @@
 #![allow(dead_code)]
 
+mod edit;
+mod index;
+mod iterate;
+mod layout;
+mod print;
+#[cfg(feature = "serde")]
+mod serialization;
+mod validate;
+mod view;
+
 use std::{
     borrow::Cow,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
     fs::File,
-    io::{self, BufReader, Cursor, ErrorKind, Read, Write},
-    path::Path,
+    io::{self, BufReader, Cursor, Read, Write},
+    path::{Path, PathBuf},
 };
 
 use byte_string::{ByteStr, ByteString};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use num::FromPrimitive;
-use num_derive::FromPrimitive;
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::helpers::{
+    binary::{BinaryLayout, UnexpectedContents},
+    offset_wrapper::{OffsetReadWrapper, OffsetWriteWrapper},
+};
 
-use crate::helpers::offset_wrapper::{OffsetReadWrapper, OffsetWriteWrapper};
+pub use edit::EditMBEFileError;
+use index::RowIndex;
+pub use index::RowKey;
+pub use iterate::{RowIterator, RowRef, SheetIterator, SheetRowIterator};
+use layout::{
+    ChunkEntry, ChunkHeader, ExpaHeader, FilePadding, PaddedString, RawField, SheetColumns,
+    SheetLayout, SheetPadding,
+};
+pub use print::{TableFormat, TablePrinter};
+pub use validate::ValidationIssue;
+pub use view::{MBEView, RowView, SheetView};
 
-type Row = Vec<TableCell>;
 type CreateRow<'a> = Vec<TableCreateCell<'a>>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnType {
-    Int = 2,
-    IntID = 9,
-    Byte = 4,
-    Float = 5,
-    String = 7,
-    StringID = 8,
+    Int,
+    IntID,
+    Byte,
+    Float,
+    String,
+    StringID,
+    /// A type this crate doesn't know, holding its value in the file. The content of these
+    /// columns is kept as raw bytes.
+    Unknown(u32),
+}
+
+/// The size and the alignment of the fields of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldLayout {
+    pub size: usize,
+    pub alignment: usize,
 }
 
 impl ColumnType {
-    /// The size of the field.
-    pub fn size(self) -> usize {
+    /// The value of the type in the file.
+    pub fn value(self) -> u32 {
+        match self {
+            Self::Int => 2,
+            Self::IntID => 9,
+            Self::Byte => 4,
+            Self::Float => 5,
+            Self::String => 7,
+            Self::StringID => 8,
+            Self::Unknown(x) => x,
+        }
+    }
+
+    pub fn from_value(value: u32) -> Self {
+        match value {
+            2 => Self::Int,
+            9 => Self::IntID,
+            4 => Self::Byte,
+            5 => Self::Float,
+            7 => Self::String,
+            8 => Self::StringID,
+            x => Self::Unknown(x),
+        }
+    }
+
+    /// The size of the field, which isn't known for [`ColumnType::Unknown`].
+    pub fn size(self) -> Option<usize> {
         match self {
-            Self::StringID | Self::String => 8,
-            Self::Int | Self::IntID | Self::Float => 4,
-            Self::Byte => 1,
+            Self::StringID | Self::String => Some(8),
+            Self::Int | Self::IntID | Self::Float => Some(4),
+            Self::Byte => Some(1),
+            Self::Unknown(_) => None,
         }
     }
 
-    /// The alignment for each field.
-    pub fn alignment(self) -> u64 {
+    /// The alignment for each field, which isn't known for [`ColumnType::Unknown`].
+    pub fn alignment(self) -> Option<u64> {
         match self {
-            Self::StringID | Self::String => 8,
-            Self::Int | Self::IntID | Self::Float => 4,
-            Self::Byte => 1,
+            Self::StringID | Self::String => Some(8),
+            Self::Int | Self::IntID | Self::Float => Some(4),
+            Self::Byte => Some(1),
+            Self::Unknown(_) => None,
         }
     }
+
+    pub fn name(self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
+            Self::Int => "Int",
+            Self::IntID => "IntID",
+            Self::Byte => "Byte",
+            Self::Float => "Float",
+            Self::String => "String",
+            Self::StringID => "StringID",
+            Self::Unknown(x) => return Cow::Owned(format!("Unknown({x})")),
+        })
+    }
+
+    pub fn from_name(value: &str) -> Option<Self> {
+        Some(match value {
+            "Int" => Self::Int,
+            "IntID" => Self::IntID,
+            "Byte" => Self::Byte,
+            "Float" => Self::Float,
+            "String" => Self::String,
+            "StringID" => Self::StringID,
+            _ => Self::Unknown(
+                value
+                    .strip_prefix("Unknown(")?
+                    .strip_suffix(')')?
+                    .parse()
+                    .ok()?,
+            ),
+        })
+    }
 }
 
+/// A cell as stored in a [`Sheet`]. String cells hold the index of their content in
+/// [`MBEFile::data`], or `None` if they don't have a `CHNK` entry.
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum TableCell {
     Int(u32),
     IntID(u32),
     Byte(u8),
     Float(f32),
-    String(u32),
-    StringID(u32),
+    String(Option<u32>),
+    StringID(Option<u32>),
+    /// A field of an unknown type, whose bytes are found in [`Row::raw`].
+    Unknown {
+        value: u32,
+        offset: u32,
+        size: u32,
+    },
 }
 
 impl TableCell {
@@ -67,6 +166,7 @@ impl TableCell {
             Self::String(_) => ColumnType::String,
             Self::Float(_) => ColumnType::Float,
             Self::Byte(_) => ColumnType::Byte,
+            Self::Unknown { value, .. } => ColumnType::Unknown(value),
         }
     }
 }
@@ -81,34 +181,112 @@ pub enum TableCreateCell<'a> {
     StringID(Cow<'a, [u8]>),
 }
 
+impl TableCreateCell<'_> {
+    pub fn type_(&self) -> ColumnType {
+        match self {
+            Self::Int(_) => ColumnType::Int,
+            Self::IntID(_) => ColumnType::IntID,
+            Self::Byte(_) => ColumnType::Byte,
+            Self::Float(_) => ColumnType::Float,
+            Self::String(_) => ColumnType::String,
+            Self::StringID(_) => ColumnType::StringID,
+        }
+    }
+}
+
 impl TableCell {
+    /// Reads a field at `offset` in the file. Until the `CHNK` section is read, string
+    /// cells hold their own offset. Fields of unknown types aren't read, as they are kept
+    /// in their row.
     fn parse(
-        row_offset: u32,
-        source: &mut OffsetReadWrapper,
+        offset: u32,
+        source: &mut impl Read,
         type_: ColumnType,
+        layout: FieldLayout,
+        field_offset: usize,
     ) -> io::Result<Self> {
         Ok(match type_ {
             ColumnType::Int => Self::Int(source.read_u32::<LittleEndian>()?),
             ColumnType::IntID => Self::IntID(source.read_u32::<LittleEndian>()?),
             ColumnType::Float => Self::Float(source.read_f32::<LittleEndian>()?),
             ColumnType::Byte => Self::Byte(source.read_u8()?),
-            ColumnType::String => {
-                let cell = Self::String(row_offset + source.offset() as u32);
-                source.read_u64::<LittleEndian>()?;
-                cell
-            }
-            ColumnType::StringID => {
-                let cell = Self::StringID(row_offset + source.offset() as u32);
-                source.read_u64::<LittleEndian>()?;
-                cell
-            }
+            ColumnType::String => Self::String(Some(offset)),
+            ColumnType::StringID => Self::StringID(Some(offset)),
+            ColumnType::Unknown(value) => Self::Unknown {
+                value,
+                offset: field_offset as u32,
+                size: layout.size as u32,
+            },
         })
     }
 
-    fn unwrap_u32(self) -> u32 {
+    fn string_index(self) -> Option<u32> {
         match self {
-            Self::Int(x) | Self::IntID(x) => x,
-            _ => panic!("tried to unwrap value {self:?}"),
+            Self::String(x) | Self::StringID(x) => x,
+            _ => None,
+        }
+    }
+
+    fn string_index_mut(&mut self) -> Option<&mut Option<u32>> {
+        match self {
+            Self::String(x) | Self::StringID(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// A row of a [`Sheet`].
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    cells: Vec<TableCell>,
+    /// The bytes the row was parsed from, so that its padding is written back as it was.
+    raw: Option<Box<[u8]>>,
+}
+
+impl Row {
+    fn new(cells: Vec<TableCell>) -> Self {
+        Self { cells, raw: None }
+    }
+}
+
+/// An entry of the `CHNK` section.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct StringEntry {
+    /// The offset of the cell the string belonged to when parsed. The actual offsets are
+    /// computed again when writing.
+    offset: u32,
+    string: ByteString,
+    /// What followed the string when parsed, up to the length given by the file.
+    padding: Option<Box<[u8]>>,
+    /// Whether the entry was parsed with the file, rather than added by an edit.
+    parsed: bool,
+}
+
+impl StringEntry {
+    fn new(string: ByteString) -> Self {
+        Self {
+            string,
+            ..Default::default()
+        }
+    }
+}
+
+/// The key rows are matched with when patching.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum RowId {
+    Int(u32),
+    String(Option<ByteString>),
+}
+
+impl RowId {
+    /// The ID of `row`, which is the value of its first column.
+    fn of(row: &Row, data: &[StringEntry]) -> Option<Self> {
+        match *row.cells.first()? {
+            TableCell::Int(x) | TableCell::IntID(x) => Some(Self::Int(x)),
+            TableCell::String(x) | TableCell::StringID(x) => {
+                Some(Self::String(x.map(|x| data[x as usize].string.clone())))
+            }
+            TableCell::Byte(_) | TableCell::Float(_) | TableCell::Unknown { .. } => None,
         }
     }
 }
@@ -121,6 +299,8 @@ pub enum PublicTableCell<'a> {
     Float(f32),
     String(Option<&'a ByteStr>),
     StringID(Option<&'a ByteStr>),
+    /// The value of the type and the bytes of a field of an unknown type.
+    Unknown(u32, &'a [u8]),
 }
 
 impl Display for PublicTableCell<'_> {
@@ -133,11 +313,24 @@ impl Display for PublicTableCell<'_> {
                 write!(f, "{}", String::from_utf8_lossy(x))
             }
             Self::String(None) | Self::StringID(None) => Ok(()),
+            Self::Unknown(_, bytes) => bytes.iter().try_for_each(|x| write!(f, "{x:02x}")),
         }
     }
 }
 
 impl<'a> PublicTableCell<'a> {
+    pub fn type_(self) -> ColumnType {
+        match self {
+            Self::Int(_) => ColumnType::Int,
+            Self::IntID(_) => ColumnType::IntID,
+            Self::Byte(_) => ColumnType::Byte,
+            Self::Float(_) => ColumnType::Float,
+            Self::String(_) => ColumnType::String,
+            Self::StringID(_) => ColumnType::StringID,
+            Self::Unknown(x, _) => ColumnType::Unknown(x),
+        }
+    }
+
     pub fn unwrap_string(self) -> Option<&'a ByteStr> {
         match self {
             Self::String(x) => x,
@@ -151,200 +344,590 @@ pub struct Sheet {
     name: ByteString,
     column_types: Vec<ColumnType>,
     rows: Vec<Row>,
+    /// `None` for sheets that weren't parsed, or whose columns changed since.
+    padding: Option<SheetPadding>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MBEFile {
     sheets: Vec<Sheet>,
-    data: Vec<(u32, ByteString)>,
+    /// The content of the `CHNK` section.
+    data: Vec<StringEntry>,
+    /// `None` for files that weren't parsed.
+    padding: Option<FilePadding>,
+    index: RowIndex,
+}
+
+#[derive(Debug)]
+pub enum PatchMBEFileError {
+    ColumnTypeMismatch {
+        sheet: String,
+        expected: Vec<ColumnType>,
+        found: Vec<ColumnType>,
+    },
+    InvalidIdColumn {
+        sheet: String,
+        column_type: Option<ColumnType>,
+    },
+}
+
+impl Display for PatchMBEFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ColumnTypeMismatch {
+                sheet,
+                expected,
+                found,
+            } => write!(
+                f,
+                "sheet {sheet}: expected column types {expected:?}, found {found:?}"
+            ),
+            Self::InvalidIdColumn {
+                sheet,
+                column_type: Some(column_type),
+            } => write!(
+                f,
+                "sheet {sheet}: the first column has type {column_type:?}, which can't be used as an ID"
+            ),
+            Self::InvalidIdColumn {
+                sheet,
+                column_type: None,
+            } => write!(f, "sheet {sheet}: there is no column to use as an ID"),
+        }
+    }
 }
+impl std::error::Error for PatchMBEFileError {}
 
+///
+/// Why an MBE file couldn't be parsed. See [`ParseMBEFileError`] for where.
+///
 #[derive(Debug)]
-pub enum ParseMBEFileError {
+pub enum ParseMBEFileErrorKind {
     BadEXPAMagicNumber,
     BadCHNKMagicNumber,
-    InvalidColumnType,
+    /// The size of a column of an unknown type couldn't be found.
+    InvalidColumnType {
+        column: usize,
+        value: u32,
+    },
+    /// The length of the rows of a sheet is too small to hold its columns.
+    RowTooShort {
+        expected: usize,
+        found: usize,
+    },
     Io(io::Error),
 }
 
-impl Display for ParseMBEFileError {
+impl Display for ParseMBEFileErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::BadEXPAMagicNumber => write!(f, "expected EXPA as a magic number"),
-            Self::InvalidColumnType => write!(f, "specified column type is not valid"),
+            Self::InvalidColumnType { column, value } => write!(
+                f,
+                "column {column}: couldn't find the size of unknown column type {value}"
+            ),
             Self::BadCHNKMagicNumber => write!(f, "expected CHNK as a magic number"),
+            Self::RowTooShort { expected, found } => write!(
+                f,
+                "rows are {found} bytes long, but their columns need {expected} bytes"
+            ),
             Self::Io(x) => write!(f, "io error: {x}"),
         }
     }
 }
-impl std::error::Error for ParseMBEFileError {}
 
-impl From<io::Error> for ParseMBEFileError {
+impl From<io::Error> for ParseMBEFileErrorKind {
     fn from(value: io::Error) -> Self {
-        Self::Io(value)
+        match UnexpectedContents::expected_by(&value) {
+            Some(b"EXPA") => Self::BadEXPAMagicNumber,
+            Some(b"CHNK") => Self::BadCHNKMagicNumber,
+            _ => Self::Io(value),
+        }
     }
 }
 
-fn read_size_prefixed_string_and_remove_nuls(
-    source: &mut OffsetReadWrapper,
-) -> io::Result<ByteString> {
-    let length = source.read_u32::<LittleEndian>()? as usize;
-    let mut string = vec![0; length];
-    source.read_exact(&mut string)?;
-    while string.pop_if(|&mut x| x == 0).is_some() {}
-    Ok(ByteString(string))
+///
+/// An error met while parsing an MBE file, with where it was met.
+///
+#[derive(Debug)]
+pub struct ParseMBEFileError {
+    kind: ParseMBEFileErrorKind,
+    /// How far the file was read when the error occurred.
+    offset: usize,
+    sheet: Option<usize>,
+    sheet_name: Option<String>,
+    row: Option<usize>,
+    path: Option<PathBuf>,
 }
 
-pub struct RowIterator<'a> {
-    file: &'a MBEFile,
-    sheet: usize,
-    row: usize,
-}
+impl ParseMBEFileError {
+    pub fn kind(&self) -> &ParseMBEFileErrorKind {
+        &self.kind
+    }
+
+    /// How far the file was read when the error occurred.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-impl RowIterator<'_> {
-    pub fn sheet(&self) -> usize {
+    /// The index of the sheet being parsed, if the error is in a sheet.
+    pub fn sheet(&self) -> Option<usize> {
         self.sheet
     }
 
-    pub fn row(&self) -> usize {
+    /// The name of the sheet being parsed, if it was read.
+    pub fn sheet_name(&self) -> Option<&str> {
+        self.sheet_name.as_deref()
+    }
+
+    /// The index of the row being parsed, if the error is in a row.
+    pub fn row(&self) -> Option<usize> {
         self.row
     }
+
+    /// The path of the file, or of the archive entry, that was parsed.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Sets the path of the file, or of the archive entry, the error comes from.
+    pub fn with_path(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..self
+        }
+    }
 }
 
-impl<'a> Iterator for RowIterator<'a> {
-    type Item = Vec<PublicTableCell<'a>>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let current_sheet = self.file.get_sheet_by_index(self.sheet)?;
-        match current_sheet.get_row(self.row) {
-            Some(x) => {
-                self.row += 1;
-                Some(x.content())
-            }
-            None => {
-                self.sheet += 1;
-                self.row = 0;
-                self.next()
-            }
+impl Display for ParseMBEFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+        match (self.sheet, &self.sheet_name) {
+            (Some(sheet), Some(name)) => write!(f, "sheet {sheet} ({name}), ")?,
+            (Some(sheet), None) => write!(f, "sheet {sheet}, ")?,
+            _ => (),
         }
+        if let Some(row) = self.row {
+            write!(f, "row {row}, ")?;
+        }
+        write!(f, "offset {:#x}: {}", self.offset, self.kind)
     }
 }
 
-impl MBEFile {
-    pub fn from_path(source: impl AsRef<Path>) -> Result<Self, ParseMBEFileError> {
-        let mut file = BufReader::new(File::open(source)?);
-        Self::parse(&mut OffsetReadWrapper::new(&mut file))
+/// An error reading the source, before anything was parsed.
+impl From<io::Error> for ParseMBEFileError {
+    fn from(value: io::Error) -> Self {
+        ParseContext::default().error(value.into(), 0)
     }
+}
 
-    pub fn rows(&self) -> RowIterator {
-        RowIterator {
-            file: self,
-            sheet: 0,
-            row: 0,
+impl std::error::Error for ParseMBEFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseMBEFileErrorKind::Io(x) => Some(x),
+            _ => None,
         }
     }
+}
 
-    pub fn parse(source: &mut OffsetReadWrapper) -> Result<Self, ParseMBEFileError> {
-        let mut magic_number = [0; 4];
-        source.read_exact(&mut magic_number)?;
-        if &magic_number != b"EXPA" {
-            return Err(ParseMBEFileError::BadEXPAMagicNumber);
+/// Where the parser is in a file, to tell where errors occur.
+#[derive(Debug, Default)]
+struct ParseContext {
+    sheet: Option<usize>,
+    sheet_name: Option<String>,
+    row: Option<usize>,
+}
+
+impl ParseContext {
+    fn error(self, kind: ParseMBEFileErrorKind, offset: usize) -> ParseMBEFileError {
+        ParseMBEFileError {
+            kind,
+            offset,
+            sheet: self.sheet,
+            sheet_name: self.sheet_name,
+            row: self.row,
+            path: None,
         }
+    }
+}
 
-        let number_of_sheet = source.read_u32::<LittleEndian>()?;
-        let sheets = (0..number_of_sheet)
-            .map(|_| {
-                let name = read_size_prefixed_string_and_remove_nuls(source)?;
-                let nb_columns = source.read_u32::<LittleEndian>()?;
-                let Some(column_types) = (0..nb_columns)
-                    .map(|_| Ok(ColumnType::from_u32(source.read_u32::<LittleEndian>()?)))
-                    .collect::<Result<Option<Vec<_>>, io::Error>>()?
-                else {
-                    return Err(ParseMBEFileError::InvalidColumnType);
+#[derive(Debug)]
+pub enum VerifyRoundtripError {
+    Parse(ParseMBEFileError),
+    Io(io::Error),
+    /// The written file differs from the original, starting at `offset`.
+    Mismatch {
+        offset: usize,
+        original_length: usize,
+        written_length: usize,
+    },
+}
+
+impl Display for VerifyRoundtripError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(x) => write!(f, "couldn't parse the file: {x}"),
+            Self::Io(x) => write!(f, "io error: {x}"),
+            Self::Mismatch {
+                offset,
+                original_length,
+                written_length,
+            } => write!(
+                f,
+                "the written file differs from the original at offset {offset:#x} (original is {original_length} bytes long, written is {written_length} bytes long)"
+            ),
+        }
+    }
+}
+impl std::error::Error for VerifyRoundtripError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(x) => Some(x),
+            Self::Io(x) => Some(x),
+            Self::Mismatch { .. } => None,
+        }
+    }
+}
+
+impl From<ParseMBEFileError> for VerifyRoundtripError {
+    fn from(value: ParseMBEFileError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl From<io::Error> for VerifyRoundtripError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Default for MBEFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MBEFile {
+    /// Creates a file without any sheet.
+    pub const fn new() -> Self {
+        Self {
+            sheets: Vec::new(),
+            data: Vec::new(),
+            padding: None,
+            index: RowIndex::new(),
+        }
+    }
+
+    pub fn from_path(source: impl AsRef<Path>) -> Result<Self, ParseMBEFileError> {
+        let source = source.as_ref();
+        let mut file = BufReader::new(
+            File::open(source).map_err(|x| ParseMBEFileError::from(x).with_path(source))?,
+        );
+        Self::parse(&mut OffsetReadWrapper::new(&mut file)).map_err(|x| x.with_path(source))
+    }
+
+    pub fn parse(source: &mut OffsetReadWrapper) -> Result<Self, ParseMBEFileError> {
+        Self::parse_with_unknown_layouts(source, &HashMap::new())
+    }
+
+    ///
+    /// Like [`MBEFile::parse`], with the layout of column types this crate doesn't know.
+    ///
+    /// The layout of unknown types that aren't in `unknown_layouts` is guessed from the
+    /// length of the rows, which only works if there are few of them in a sheet.
+    ///
+    pub fn parse_with_unknown_layouts(
+        source: &mut OffsetReadWrapper,
+        unknown_layouts: &HashMap<u32, FieldLayout>,
+    ) -> Result<Self, ParseMBEFileError> {
+        let mut context = ParseContext::default();
+        Self::parse_in_context(source, unknown_layouts, &mut context)
+            .map_err(|kind| context.error(kind, source.offset()))
+    }
+
+    fn parse_in_context(
+        source: &mut OffsetReadWrapper,
+        unknown_layouts: &HashMap<u32, FieldLayout>,
+        context: &mut ParseContext,
+    ) -> Result<Self, ParseMBEFileErrorKind> {
+        let header = ExpaHeader::read_from(source)?;
+        let sheets = (0..header.sheet_count as usize)
+            .map(|sheet| {
+                context.sheet = Some(sheet);
+                context.sheet_name = None;
+                let PaddedString {
+                    string: name,
+                    padding: name_padding,
+                } = PaddedString::read_from(source)?;
+                context.sheet_name = Some(String::from_utf8_lossy(&name).into_owned());
+                let SheetColumns {
+                    column_types,
+                    row_length,
+                    row_count: row_number,
+                } = SheetColumns::read_from(source)?;
+                let row_length = row_length as usize;
+                let unknown_layouts =
+                    layout::unknown_layouts(&column_types, row_length, unknown_layouts).map_err(
+                        |column| ParseMBEFileErrorKind::InvalidColumnType {
+                            column,
+                            value: column_types[column].value(),
+                        },
+                    )?;
+                let padding = SheetPadding {
+                    name: name_padding,
+                    rows_alignment: source.read_alignment(8)?.into_boxed_slice(),
+                    row_length,
+                    unknown_layouts,
                 };
-                let row_length = source.read_u32::<LittleEndian>()?;
-                let row_number = source.read_u32::<LittleEndian>()?;
-                let mut row_buffer = vec![0; row_length as usize];
-                source.align(8)?;
-                let rows = (0..row_number)
-                    .map(|_| {
-                        let row_offset = source.offset() as u32;
-                        source.read_exact(&mut row_buffer)?;
-                        let mut source = Cursor::new(&row_buffer);
-                        let mut source = OffsetReadWrapper::new(&mut source);
-                        column_types
-                            .iter()
-                            .map(|&column_type| {
-                                source.align(column_type.alignment())?;
-                                TableCell::parse(row_offset, &mut source, column_type)
-                            })
-                            .collect::<io::Result<Vec<_>>>()
-                    })
-                    .collect::<io::Result<Vec<_>>>()?;
+                let rows = SheetLayout::new(&name, &column_types, Some(&padding)).read_rows(
+                    source,
+                    &column_types,
+                    row_number as usize,
+                    &mut context.row,
+                )?;
                 Ok(Sheet {
                     name,
                     column_types,
                     rows,
+                    padding: Some(padding),
                 })
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Result<Vec<_>, ParseMBEFileErrorKind>>()?;
+        context.sheet = None;
+        context.sheet_name = None;
 
-        source.align(8)?;
-        if matches!(source.read_exact(&mut magic_number), Err(x) if x.kind() == ErrorKind::UnexpectedEof)
-        {
-            return Ok(Self {
-                sheets,
-                data: Vec::new(),
-            });
-        }
-        if &magic_number != b"CHNK" {
-            return Err(ParseMBEFileError::BadCHNKMagicNumber);
+        let mut padding = FilePadding {
+            chunk_alignment: source.read_alignment(8)?.into_boxed_slice(),
+            ..Default::default()
+        };
+        let mut file = Self {
+            sheets,
+            data: Vec::new(),
+            padding: None,
+            index: RowIndex::new(),
+        };
+
+        let mut chunk_magic_number = Vec::with_capacity(4);
+        source
+            .by_ref()
+            .take(4)
+            .read_to_end(&mut chunk_magic_number)?;
+        if chunk_magic_number.len() < 4 {
+            // No `CHNK` section, just what may be left of the alignment
+            padding.trailer = chunk_magic_number;
+            file.padding = Some(padding);
+            file.resolve_string_offsets();
+            return Ok(file);
         }
-        let number_of_data = source.read_u32::<LittleEndian>()?;
-        let data = (0..number_of_data)
+        let header =
+            ChunkHeader::read_from(&mut chunk_magic_number.as_slice().chain(&mut *source))?;
+        file.data = (0..header.entry_count)
             .map(|_| {
-                let offset = source.read_u32::<LittleEndian>()?;
-                let string = read_size_prefixed_string_and_remove_nuls(source)?;
-                Ok((offset, string))
+                let ChunkEntry { offset, string } = ChunkEntry::read_from(source)?;
+                Ok(StringEntry {
+                    offset,
+                    string: string.string,
+                    padding: Some(string.padding),
+                    parsed: true,
+                })
             })
             .collect::<Result<Vec<_>, io::Error>>()?;
+        padding.has_chunk = true;
+        source.read_to_end(&mut padding.trailer)?;
+        file.padding = Some(padding);
+        file.resolve_string_offsets();
+
+        Ok(file)
+    }
 
-        Ok(Self { sheets, data })
+    /// Replaces the offsets held by string cells after parsing by the index of the `CHNK`
+    /// entry at that offset.
+    fn resolve_string_offsets(&mut self) {
+        let indices = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.offset, i as u32))
+            .collect::<HashMap<_, _>>();
+        for cell in self
+            .sheets
+            .iter_mut()
+            .flat_map(|x| x.rows.iter_mut().flat_map(|row| &mut row.cells))
+        {
+            if let Some(index) = cell.string_index_mut() {
+                *index = index.and_then(|offset| indices.get(&offset).copied());
+            }
+        }
     }
 
-    pub fn patch(mut self, mut patch: MBEFile) -> Option<MBEFile> {
-        for (i, original_sheet) in self.sheets.iter_mut().enumerate() {
-            let Some(new_sheet) = patch
-                .sheets
-                .iter_mut()
-                .find(|x| x.name == original_sheet.name)
+    /// Removes the given entries from `data`, updating the string cells referencing the
+    /// entries after them.
+    fn remove_strings(&mut self, removed: &HashSet<u32>) {
+        if removed.is_empty() {
+            return;
+        }
+        let mut new_indices = Vec::with_capacity(self.data.len());
+        let mut next_index = 0;
+        for i in 0..self.data.len() as u32 {
+            if removed.contains(&i) {
+                new_indices.push(None);
+            } else {
+                new_indices.push(Some(next_index));
+                next_index += 1;
+            }
+        }
+        let mut i = 0;
+        self.data.retain(|_| {
+            i += 1;
+            !removed.contains(&(i - 1))
+        });
+        for cell in self
+            .sheets
+            .iter_mut()
+            .flat_map(|x| x.rows.iter_mut().flat_map(|row| &mut row.cells))
+        {
+            if let Some(index) = cell.string_index_mut() {
+                *index = index.and_then(|x| new_indices[x as usize]);
+            }
+        }
+    }
+
+    /// Moves the strings referenced by `row` from `source` to `self.data`.
+    fn import_row(&mut self, mut row: Row, source: &mut [StringEntry]) -> Row {
+        for cell in &mut row.cells {
+            if let Some(index) = cell.string_index_mut()
+                && let Some(source_index) = *index
+            {
+                let string = StringEntry {
+                    parsed: false,
+                    ..std::mem::take(&mut source[source_index as usize])
+                };
+                *index = Some(self.data.len() as u32);
+                self.data.push(string);
+            }
+        }
+        row
+    }
+
+    ///
+    /// Merges `patch` into this file.
+    ///
+    /// Sheets are matched by name, and rows by the value of their first column: rows of
+    /// `patch` replace the rows with the same ID, and rows with a new ID are inserted before
+    /// the first row with a greater ID. Sheets only found in `patch` are added at the end.
+    ///
+    pub fn patch(mut self, mut patch: MBEFile) -> Result<MBEFile, PatchMBEFileError> {
+        let mut patch_data = std::mem::take(&mut patch.data);
+        let mut removed_strings = HashSet::new();
+        self.invalidate_index();
+
+        for new_sheet in patch.sheets {
+            let sheet_name = String::from_utf8_lossy(&new_sheet.name).into_owned();
+            let Some(sheet_index) = self.sheets.iter().position(|x| x.name == new_sheet.name)
             else {
-                log::info!(
-                    "patch: skipping sheet {} because the patch doesn't contain a similarly named sheet",
-                    i + 1
-                );
+                log::info!("patch: adding sheet {sheet_name}");
+                let rows = new_sheet
+                    .rows
+                    .into_iter()
+                    .map(|row| self.import_row(row, &mut patch_data))
+                    .collect();
+                self.sheets.push(Sheet { rows, ..new_sheet });
                 continue;
             };
 
+            let original_sheet = &self.sheets[sheet_index];
             if original_sheet.column_types != new_sheet.column_types {
-                return None;
+                return Err(PatchMBEFileError::ColumnTypeMismatch {
+                    sheet: sheet_name,
+                    expected: original_sheet.column_types.clone(),
+                    found: new_sheet.column_types,
+                });
+            }
+            match new_sheet.column_types.first() {
+                Some(ColumnType::Byte | ColumnType::Float | ColumnType::Unknown(_)) | None => {
+                    return Err(PatchMBEFileError::InvalidIdColumn {
+                        sheet: sheet_name,
+                        column_type: new_sheet.column_types.first().copied(),
+                    });
+                }
+                _ => (),
             }
 
-            let mut new_rows = Vec::new();
-            let original_rows = original_sheet.rows.drain(..);
-            let mut patch_rows = new_sheet.rows.drain(..).peekable();
-            for original_row in original_rows {
-                let id = original_row[0].unwrap_u32();
-                while let Some(patch_row) = patch_rows.next_if(|row| row[0].unwrap_u32() < id) {
-                    new_rows.push(patch_row);
+            let ids = original_sheet
+                .rows
+                .iter()
+                .map(|row| RowId::of(row, &self.data))
+                .collect::<Vec<_>>();
+            let (mut replaced, mut added, mut unchanged) = (0, 0, 0);
+            let mut new_rows = original_sheet
+                .rows
+                .iter()
+                .cloned()
+                .map(Some)
+                .collect::<Vec<_>>();
+            let mut inserted = Vec::new();
+
+            for patch_row in new_sheet.rows {
+                let patch_id = RowId::of(&patch_row, &patch_data);
+
+                match ids.iter().position(|id| id.is_some() && *id == patch_id) {
+                    Some(position) => {
+                        let original_row = new_rows[position].take().unwrap();
+                        // Compares the strings and the bytes of unknown fields, not where
+                        // they are stored
+                        let same_content =
+                            original_row
+                                .cells
+                                .iter()
+                                .zip(&patch_row.cells)
+                                .all(|(&a, &b)| {
+                                    cell_to_public(a, original_row.raw.as_deref(), &self.data)
+                                        == cell_to_public(b, patch_row.raw.as_deref(), &patch_data)
+                                });
+                        if same_content {
+                            unchanged += 1;
+                            new_rows[position] = Some(original_row);
+                            continue;
+                        }
+                        replaced += 1;
+                        removed_strings
+                            .extend(original_row.cells.iter().filter_map(|x| x.string_index()));
+                        let patch_row = self.import_row(patch_row, &mut patch_data);
+                        new_rows[position] = Some(patch_row);
+                    }
+                    None => {
+                        added += 1;
+                        let position = ids
+                            .iter()
+                            .position(|id| id.is_some() && *id > patch_id)
+                            .unwrap_or(ids.len());
+                        inserted.push((position, self.import_row(patch_row, &mut patch_data)));
+                    }
                 }
-                if let Some(patch_row) = patch_rows.next_if(|row| row[0].unwrap_u32() == id) {
-                    new_rows.push(patch_row);
-                } else {
-                    new_rows.push(original_row);
+            }
+
+            // Stable sort, so rows inserted at the same position keep the order of the patch
+            inserted.sort_by_key(|&(position, _)| position);
+            let mut inserted = inserted.into_iter().peekable();
+            let mut rows = Vec::with_capacity(new_rows.len() + added);
+            for (i, row) in new_rows.into_iter().enumerate() {
+                while let Some((_, row)) = inserted.next_if(|&(position, _)| position == i) {
+                    rows.push(row);
                 }
+                rows.extend(row);
             }
+            rows.extend(inserted.map(|(_, row)| row));
+
+            log::info!(
+                "patch: sheet {sheet_name}: {added} rows added, {replaced} replaced, {unchanged} unchanged"
+            );
+            self.sheets[sheet_index].rows = rows;
         }
-        todo!()
+
+        self.remove_strings(&removed_strings);
+        Ok(self)
     }
 
     pub fn get_sheet_by_name(&self, name: &[u8]) -> Option<RowSelectioner<'_>> {
@@ -355,6 +938,10 @@ impl MBEFile {
         })
     }
 
+    pub fn number_of_sheets(&self) -> usize {
+        self.sheets.len()
+    }
+
     pub fn get_sheet_by_index(&self, index: usize) -> Option<RowSelectioner<'_>> {
         if self.sheets.len() <= index {
             None
@@ -366,6 +953,13 @@ impl MBEFile {
         }
     }
 
+    ///
+    /// Sets the content of a string cell, returning `None` if the cell doesn't exist or
+    /// isn't a string.
+    ///
+    /// Cells that were empty get a new `CHNK` entry, which is written in order with the
+    /// others.
+    ///
     pub fn modify_string(
         &mut self,
         sheet: usize,
@@ -373,94 +967,136 @@ impl MBEFile {
         column: usize,
         new_content: Vec<u8>,
     ) -> Option<()> {
-        let mut offset = 8;
-        for skipped_sheet in self.sheets.iter().take(sheet) {
-            offset += skipped_sheet.name.len().next_multiple_of(4)
-                + 4 * (skipped_sheet.column_types.len() + 4);
-            offset = offset.next_multiple_of(8);
-            let mut one_line = 0_usize;
-            for col_type in &skipped_sheet.column_types {
-                one_line = one_line.next_multiple_of(col_type.alignment() as usize);
-                one_line += col_type.size();
-            }
-            offset += skipped_sheet.rows.len() * one_line;
-        }
+        let cell = self.sheets.get(sheet)?.rows.get(row)?.cells.get(column)?;
+        let value = match cell.type_() {
+            ColumnType::String => TableCreateCell::String(Cow::Owned(new_content)),
+            ColumnType::StringID => TableCreateCell::StringID(Cow::Owned(new_content)),
+            _ => return None,
+        };
+        self.set_cell(sheet, row, column, value).ok()
+    }
 
-        let sheet = self.sheets.get(sheet)?;
-        offset += (sheet.name.len() + 1).next_multiple_of(4) + 4 * (sheet.column_types.len() + 4);
-        offset = offset.next_multiple_of(8);
+    ///
+    /// Writes the `EXPA` section, returning the offset of the cell referencing each string,
+    /// if any.
+    ///
+    fn write_sheets(&self, writer: &mut OffsetWriteWrapper) -> io::Result<Vec<Option<u32>>> {
+        ExpaHeader {
+            sheet_count: self.sheets.len() as u32,
+        }
+        .write_to(writer)?;
+        let mut string_offsets = vec![None; self.data.len()];
 
-        let mut one_line = 0_usize;
-        let mut up_to_column = 0_usize;
-        for (i, col_type) in sheet.column_types.iter().enumerate() {
-            one_line = one_line.next_multiple_of(col_type.alignment() as usize);
-            if i == column {
-                up_to_column = one_line;
+        for sheet in &self.sheets {
+            let layout = SheetLayout::new(&sheet.name, &sheet.column_types, sheet.padding.as_ref());
+            layout.write_header(
+                writer,
+                &sheet.name,
+                &sheet.column_types,
+                sheet.rows.len(),
+                sheet.padding.as_ref(),
+            )?;
+            for row in &sheet.rows {
+                let row_offset = layout.write_row(
+                    writer,
+                    row.cells.iter().map(RawField::from),
+                    row.raw.as_deref(),
+                )?;
+                for (column, cell) in row.cells.iter().enumerate() {
+                    if let Some(x) = cell.string_index() {
+                        string_offsets[x as usize] =
+                            Some((row_offset + layout.field_offset(column)) as u32);
+                    }
+                }
             }
-            one_line += col_type.size();
-        }
-        offset += row * one_line + up_to_column;
-        if let Ok(x) = self.data.binary_search_by_key(&(offset as u32), |x| x.0) {
-            self.data[x].1 = ByteString(new_content);
-            Some(())
-        } else {
-            None
         }
+
+        Ok(string_offsets)
+    }
+
+    /// The offset each string will have in the `CHNK` section once the file is written.
+    fn string_offsets(&self) -> Vec<Option<u32>> {
+        self.write_sheets(&mut OffsetWriteWrapper::new(&mut io::sink()))
+            .expect("writing to a sink shouldn't fail")
     }
 
     pub fn write(&self, writer: &mut OffsetWriteWrapper) -> io::Result<()> {
-        writer.write_all(b"EXPA")?;
-        writer.write_u32::<LittleEndian>(self.sheets.len() as u32)?;
+        let string_offsets = self.write_sheets(writer)?;
 
-        for sheet in &self.sheets {
-            write_size_prefixed_string(ByteStr::new(&sheet.name), writer, 0)?;
-            writer.write_u32::<LittleEndian>(sheet.column_types.len() as u32)?;
-            let mut sink = io::sink();
-            let mut length = OffsetWriteWrapper::new(&mut sink);
-            for type_ in &sheet.column_types {
-                writer.write_u32::<LittleEndian>(*type_ as u32)?;
-                length.align(type_.alignment(), 0)?;
-                match type_ {
-                    ColumnType::StringID | ColumnType::String => {
-                        length.write_u64::<LittleEndian>(0)?
-                    }
-                    ColumnType::Float | ColumnType::Int | ColumnType::IntID => {
-                        length.write_u32::<LittleEndian>(0)?
-                    }
-                    ColumnType::Byte => length.write_u8(0)?,
-                }
-            }
-            writer.write_u32::<LittleEndian>(length.offset() as u32)?;
-            writer.write_u32::<LittleEndian>(sheet.rows.len() as u32)?;
-            writer.align(8, 0)?;
-            for column in sheet.rows.iter().flatten() {
-                writer.align(column.type_().alignment(), 0xcc)?;
-                match column {
-                    &TableCell::Int(x) | &TableCell::IntID(x) => {
-                        writer.write_u32::<LittleEndian>(x)?
-                    }
-                    &TableCell::Byte(x) => writer.write_u8(x)?,
-                    &TableCell::Float(x) => writer.write_f32::<LittleEndian>(x)?,
-                    TableCell::String(_) | TableCell::StringID(_) => {
-                        writer.write_u64::<LittleEndian>(0)?;
-                    }
-                }
-            }
+        // Strings no cell refers to keep the offset they were parsed with. Parsed entries
+        // keep their order, so that unedited files are written back as they were, and new
+        // ones go before the first entry with a greater offset
+        let (mut data, mut added): (Vec<_>, Vec<_>) = self
+            .data
+            .iter()
+            .zip(string_offsets)
+            .map(|(entry, new_offset)| (new_offset.unwrap_or(entry.offset), entry))
+            .partition(|(_, entry)| entry.parsed);
+        added.sort_by_key(|&(offset, _)| offset);
+        for entry in added {
+            let position = data
+                .iter()
+                .position(|&(offset, _)| offset > entry.0)
+                .unwrap_or(data.len());
+            data.insert(position, entry);
         }
 
-        writer.align(8, 0)?;
-        writer.write_all(b"CHNK")?;
-        writer.write_u32::<LittleEndian>(self.data.len() as u32)?;
+        let padding = self.padding.clone().unwrap_or_default();
+        if self.padding.is_some() && !padding.has_chunk && data.is_empty() {
+            // The file had no `CHNK` section, and still doesn't need one
+            writer.write_all(&padding.chunk_alignment)?;
+            writer.write_all(&padding.trailer)?;
+            return Ok(());
+        }
+        if padding.chunk_alignment.len() == writer.offset().next_multiple_of(8) - writer.offset() {
+            writer.write_all(&padding.chunk_alignment)?;
+        } else {
+            writer.align(8, 0)?;
+        }
+        ChunkHeader {
+            entry_count: data.len() as u32,
+        }
+        .write_to(writer)?;
 
-        for (id, string) in &self.data {
-            writer.write_u32::<LittleEndian>(*id)?;
-            write_size_prefixed_string(ByteStr::new(string), writer, 1)?;
+        for (offset, entry) in data {
+            ChunkEntry {
+                offset,
+                string: PaddedString::new(&entry.string, 1, entry.padding.as_deref()),
+            }
+            .write_to(writer)?;
         }
+        writer.write_all(&padding.trailer)?;
 
         Ok(())
     }
 }
 
+///
+/// Parses `bytes` as an MBE file and writes it back, returning where the result differs
+/// from `bytes`.
+///
+/// This tells whether a difference in a repacked file comes from edits or from the way
+/// files are written.
+///
+pub fn verify_roundtrip(bytes: &[u8]) -> Result<(), VerifyRoundtripError> {
+    let file = MBEFile::parse(&mut OffsetReadWrapper::new(&mut Cursor::new(bytes)))?;
+    let mut written = Vec::with_capacity(bytes.len());
+    file.write(&mut OffsetWriteWrapper::new(&mut written))?;
+    if written == bytes {
+        return Ok(());
+    }
+    let offset = written
+        .iter()
+        .zip(bytes)
+        .position(|(a, b)| a != b)
+        .unwrap_or(written.len().min(bytes.len()));
+    Err(VerifyRoundtripError::Mismatch {
+        offset,
+        original_length: bytes.len(),
+        written_length: written.len(),
+    })
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct RowSelectioner<'a> {
     sheet_index: usize,
@@ -484,6 +1120,10 @@ impl<'a> RowSelectioner<'a> {
         self.file
     }
 
+    pub fn name(self) -> &'a ByteStr {
+        ByteStr::new(&self.file.sheets[self.sheet_index].name)
+    }
+
     pub fn column_types(self) -> &'a [ColumnType] {
         &self.file.sheets[self.sheet_index].column_types
     }
@@ -503,40 +1143,46 @@ pub struct ColumnSelectioner<'a> {
     file: &'a MBEFile,
 }
 
-fn cell_to_public(cell: TableCell, data: &[(u32, ByteString)]) -> PublicTableCell {
+fn cell_to_public<'a>(
+    cell: TableCell,
+    raw: Option<&'a [u8]>,
+    data: &'a [StringEntry],
+) -> PublicTableCell<'a> {
     match cell {
         TableCell::Float(x) => PublicTableCell::Float(x),
         TableCell::Int(x) => PublicTableCell::Int(x),
         TableCell::IntID(x) => PublicTableCell::IntID(x),
         TableCell::Byte(x) => PublicTableCell::Byte(x),
         TableCell::String(x) => {
-            if let Ok(idx) = data.binary_search_by_key(&x, |(x, _)| *x) {
-                PublicTableCell::String(Some(ByteStr::new(data[idx].1.as_slice())))
-            } else {
-                PublicTableCell::String(None)
-            }
+            PublicTableCell::String(x.map(|x| ByteStr::new(data[x as usize].string.as_slice())))
         }
         TableCell::StringID(x) => {
-            if let Ok(idx) = data.binary_search_by_key(&x, |(x, _)| *x) {
-                PublicTableCell::StringID(Some(ByteStr::new(data[idx].1.as_slice())))
-            } else {
-                PublicTableCell::StringID(None)
-            }
+            PublicTableCell::StringID(x.map(|x| ByteStr::new(data[x as usize].string.as_slice())))
         }
+        TableCell::Unknown {
+            value,
+            offset,
+            size,
+        } => PublicTableCell::Unknown(
+            value,
+            raw.map_or(&[], |x| &x[offset as usize..(offset + size) as usize]),
+        ),
     }
 }
 
 impl<'a> ColumnSelectioner<'a> {
     pub fn content(self) -> Vec<PublicTableCell<'a>> {
-        self.file.sheets[self.sheet_index].rows[self.row_index]
+        let row = &self.file.sheets[self.sheet_index].rows[self.row_index];
+        row.cells
             .iter()
-            .map(|&cell| cell_to_public(cell, &self.file.data))
+            .map(|&cell| cell_to_public(cell, row.raw.as_deref(), &self.file.data))
             .collect()
     }
 
     pub fn get_column(self, index: usize) -> Option<PublicTableCell<'a>> {
-        let cell = *self.file.sheets[self.sheet_index].rows[self.row_index].get(index)?;
-        Some(cell_to_public(cell, &self.file.data))
+        let row = &self.file.sheets[self.sheet_index].rows[self.row_index];
+        let cell = *row.cells.get(index)?;
+        Some(cell_to_public(cell, row.raw.as_deref(), &self.file.data))
     }
 }
 
@@ -550,69 +1196,48 @@ pub struct MBEFileCreator<'a> {
     pub sheets: Vec<SheetCreator<'a>>,
 }
 
-fn write_size_prefixed_string(
-    str: &ByteStr,
-    writer: &mut OffsetWriteWrapper,
-    nul_byte: usize,
-) -> std::io::Result<()> {
-    let len = str.len() + nul_byte; // accounting for nul byte
-    let pad = 4 - len % 4;
-    let padded_len = len + pad;
-    writer.write_u32::<LittleEndian>(padded_len as u32)?;
-    writer.write_all(str)?;
-    io::copy(&mut io::repeat(0).take((pad + nul_byte) as u64), writer)?;
-    Ok(())
-}
-
 impl MBEFileCreator<'_> {
     pub fn write(&self, writer: &mut OffsetWriteWrapper) -> io::Result<()> {
-        writer.write_all(b"EXPA")?;
-        writer.write_u32::<LittleEndian>(self.sheets.len() as u32)?;
+        ExpaHeader {
+            sheet_count: self.sheets.len() as u32,
+        }
+        .write_to(writer)?;
         let mut data = Vec::new();
 
         for sheet in &self.sheets {
-            write_size_prefixed_string(ByteStr::new(&sheet.name), writer, 0)?;
-            writer.write_u32::<LittleEndian>(sheet.column_types.len() as u32)?;
-            let mut sink = io::sink();
-            let mut length = OffsetWriteWrapper::new(&mut sink);
-            for type_ in &sheet.column_types {
-                writer.write_u32::<LittleEndian>(*type_ as u32)?;
-                length.align(type_.alignment(), 0)?;
-                match type_ {
-                    ColumnType::StringID | ColumnType::String => {
-                        length.write_u64::<LittleEndian>(0)?
-                    }
-                    ColumnType::Float | ColumnType::Int | ColumnType::IntID => {
-                        length.write_u32::<LittleEndian>(0)?
-                    }
-                    ColumnType::Byte => length.write_u8(0)?,
-                }
-            }
-            length.align(4, 0)?;
-            writer.write_u32::<LittleEndian>(length.offset() as u32)?;
-            writer.write_u32::<LittleEndian>(sheet.rows.len() as u32)?;
-            writer.align(8, 0xcc)?;
-            for column in sheet.rows.iter().flatten() {
-                match column {
-                    &TableCreateCell::Int(x) | &TableCreateCell::IntID(x) => {
-                        writer.write_u32::<LittleEndian>(x)?
-                    }
-                    &TableCreateCell::Byte(x) => writer.write_u8(x)?,
-                    &TableCreateCell::Float(x) => writer.write_f32::<LittleEndian>(x)?,
-                    TableCreateCell::String(x) | TableCreateCell::StringID(x) => {
-                        data.push((writer.offset() as u32, ByteStr::new(x)));
-                        writer.write_u64::<LittleEndian>(0)?;
+            let layout = SheetLayout::new(&sheet.name, &sheet.column_types, None);
+            layout.write_header(
+                writer,
+                &sheet.name,
+                &sheet.column_types,
+                sheet.rows.len(),
+                None,
+            )?;
+            for row in &sheet.rows {
+                let row_offset = layout.write_row(writer, row.iter().map(RawField::from), None)?;
+                for (column, cell) in row.iter().enumerate() {
+                    if let TableCreateCell::String(x) | TableCreateCell::StringID(x) = cell {
+                        data.push((
+                            (row_offset + layout.field_offset(column)) as u32,
+                            ByteStr::new(x),
+                        ));
                     }
                 }
             }
         }
 
-        writer.write_all(b"CHNK")?;
-        writer.write_u32::<LittleEndian>(data.len() as u32)?;
+        writer.align(8, 0)?;
+        ChunkHeader {
+            entry_count: data.len() as u32,
+        }
+        .write_to(writer)?;
 
-        for (id, string) in data {
-            writer.write_u32::<LittleEndian>(id)?;
-            write_size_prefixed_string(string, writer, 1)?;
+        for (offset, string) in data {
+            ChunkEntry {
+                offset,
+                string: PaddedString::new(string, 1, None),
+            }
+            .write_to(writer)?;
         }
 
         Ok(())
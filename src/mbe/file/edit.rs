@@ -0,0 +1,342 @@
+use std::{collections::HashSet, fmt::Display};
+
+use byte_string::ByteString;
+
+use super::{ColumnType, MBEFile, Row, Sheet, StringEntry, TableCell, TableCreateCell};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditMBEFileError {
+    SheetOutOfBounds(usize),
+    RowOutOfBounds {
+        sheet: usize,
+        row: usize,
+    },
+    ColumnOutOfBounds {
+        sheet: usize,
+        column: usize,
+    },
+    TypeMismatch {
+        expected: ColumnType,
+        found: ColumnType,
+    },
+    WrongNumberOfCells {
+        expected: usize,
+        found: usize,
+    },
+    DuplicateSheetName(ByteString),
+    /// Columns of unknown types can't be created, and their content would be lost if the
+    /// layout of their sheet changed.
+    UnknownColumnType(ColumnType),
+}
+
+impl Display for EditMBEFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SheetOutOfBounds(sheet) => write!(f, "there is no sheet {sheet}"),
+            Self::RowOutOfBounds { sheet, row } => write!(f, "sheet {sheet} has no row {row}"),
+            Self::ColumnOutOfBounds { sheet, column } => {
+                write!(f, "sheet {sheet} has no column {column}")
+            }
+            Self::TypeMismatch { expected, found } => {
+                write!(f, "expected a cell of type {expected:?}, found {found:?}")
+            }
+            Self::WrongNumberOfCells { expected, found } => {
+                write!(f, "expected {expected} cells, found {found}")
+            }
+            Self::DuplicateSheetName(name) => write!(f, "there already is a sheet named {name:?}"),
+            Self::UnknownColumnType(column_type) => write!(
+                f,
+                "columns of type {} can't be created or moved",
+                column_type.name()
+            ),
+        }
+    }
+}
+impl std::error::Error for EditMBEFileError {}
+
+impl MBEFile {
+    fn check_sheet(&self, sheet: usize) -> Result<&Sheet, EditMBEFileError> {
+        self.sheets
+            .get(sheet)
+            .ok_or(EditMBEFileError::SheetOutOfBounds(sheet))
+    }
+
+    fn check_row(&self, sheet: usize, row: usize) -> Result<(), EditMBEFileError> {
+        if self.check_sheet(sheet)?.rows.len() <= row {
+            return Err(EditMBEFileError::RowOutOfBounds { sheet, row });
+        }
+        Ok(())
+    }
+
+    fn check_cells(
+        column_types: &[ColumnType],
+        cells: &[TableCreateCell],
+    ) -> Result<(), EditMBEFileError> {
+        if column_types.len() != cells.len() {
+            return Err(EditMBEFileError::WrongNumberOfCells {
+                expected: column_types.len(),
+                found: cells.len(),
+            });
+        }
+        for (&expected, cell) in column_types.iter().zip(cells) {
+            if expected != cell.type_() {
+                return Err(EditMBEFileError::TypeMismatch {
+                    expected,
+                    found: cell.type_(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if there is a column of an unknown type in `column_types`.
+    fn check_known_types(column_types: &[ColumnType]) -> Result<(), EditMBEFileError> {
+        match column_types
+            .iter()
+            .find(|x| matches!(x, ColumnType::Unknown(_)))
+        {
+            Some(&column_type) => Err(EditMBEFileError::UnknownColumnType(column_type)),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops what was kept of the layout of a sheet whose columns changed.
+    fn forget_padding(&mut self, sheet: usize) {
+        let sheet = &mut self.sheets[sheet];
+        sheet.padding = None;
+        for row in &mut sheet.rows {
+            row.raw = None;
+        }
+    }
+
+    /// Converts `cell`, adding its string to `data` if needed. Empty strings don't get a
+    /// `CHNK` entry, like in the game's files.
+    fn store_cell(&mut self, cell: TableCreateCell) -> TableCell {
+        let mut store_string = |string: &[u8]| {
+            if string.is_empty() {
+                None
+            } else {
+                self.data
+                    .push(StringEntry::new(ByteString(string.to_vec())));
+                Some(self.data.len() as u32 - 1)
+            }
+        };
+        match cell {
+            TableCreateCell::Int(x) => TableCell::Int(x),
+            TableCreateCell::IntID(x) => TableCell::IntID(x),
+            TableCreateCell::Byte(x) => TableCell::Byte(x),
+            TableCreateCell::Float(x) => TableCell::Float(x),
+            TableCreateCell::String(x) => TableCell::String(store_string(&x)),
+            TableCreateCell::StringID(x) => TableCell::StringID(store_string(&x)),
+        }
+    }
+
+    ///
+    /// Replaces the value of a cell. `value` must have the type of the column.
+    ///
+    pub fn set_cell(
+        &mut self,
+        sheet: usize,
+        row: usize,
+        column: usize,
+        value: TableCreateCell,
+    ) -> Result<(), EditMBEFileError> {
+        self.check_row(sheet, row)?;
+        let Some(&expected) = self.sheets[sheet].column_types.get(column) else {
+            return Err(EditMBEFileError::ColumnOutOfBounds { sheet, column });
+        };
+        if expected != value.type_() {
+            return Err(EditMBEFileError::TypeMismatch {
+                expected,
+                found: value.type_(),
+            });
+        }
+
+        if column == 0 {
+            self.invalidate_index();
+        }
+        let previous = self.sheets[sheet].rows[row].cells[column];
+        if let Some(index) = previous.string_index()
+            && let TableCreateCell::String(string) | TableCreateCell::StringID(string) = &value
+            && !string.is_empty()
+        {
+            // Reuse the existing entry, so that the order of `CHNK` is kept
+            self.data[index as usize] = StringEntry {
+                string: ByteString(string.to_vec()),
+                padding: None,
+                ..self.data[index as usize]
+            };
+            return Ok(());
+        }
+
+        let cell = self.store_cell(value);
+        self.sheets[sheet].rows[row].cells[column] = cell;
+        if let Some(index) = previous.string_index() {
+            self.remove_strings(&HashSet::from([index]));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Gives a string cell a `CHNK` entry holding an empty string, which [`Self::set_cell`]
+    /// doesn't create.
+    ///
+    pub fn set_empty_string(
+        &mut self,
+        sheet: usize,
+        row: usize,
+        column: usize,
+    ) -> Result<(), EditMBEFileError> {
+        self.check_row(sheet, row)?;
+        let Some(&expected) = self.sheets[sheet].column_types.get(column) else {
+            return Err(EditMBEFileError::ColumnOutOfBounds { sheet, column });
+        };
+        let data_len = self.data.len() as u32;
+        let Some(index) = self.sheets[sheet].rows[row].cells[column].string_index_mut() else {
+            return Err(EditMBEFileError::TypeMismatch {
+                expected,
+                found: ColumnType::String,
+            });
+        };
+        match *index {
+            Some(index) => {
+                self.data[index as usize] = StringEntry {
+                    string: ByteString(Vec::new()),
+                    padding: None,
+                    ..self.data[index as usize]
+                }
+            }
+            None => {
+                *index = Some(data_len);
+                self.data.push(StringEntry::new(ByteString(Vec::new())));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Inserts a row at position `row`, shifting all rows after it. `cells` must match the
+    /// column types of the sheet.
+    ///
+    pub fn insert_row(
+        &mut self,
+        sheet: usize,
+        row: usize,
+        cells: Vec<TableCreateCell>,
+    ) -> Result<(), EditMBEFileError> {
+        let current_sheet = self.check_sheet(sheet)?;
+        if current_sheet.rows.len() < row {
+            return Err(EditMBEFileError::RowOutOfBounds { sheet, row });
+        }
+        Self::check_cells(&current_sheet.column_types, &cells)?;
+
+        let cells = cells
+            .into_iter()
+            .map(|cell| self.store_cell(cell))
+            .collect();
+        self.sheets[sheet].rows.insert(row, Row::new(cells));
+        self.invalidate_index();
+        Ok(())
+    }
+
+    pub fn remove_row(&mut self, sheet: usize, row: usize) -> Result<(), EditMBEFileError> {
+        self.check_row(sheet, row)?;
+        let removed = self.sheets[sheet].rows.remove(row);
+        self.invalidate_index();
+        self.remove_strings(
+            &removed
+                .cells
+                .iter()
+                .filter_map(|x| x.string_index())
+                .collect(),
+        );
+        Ok(())
+    }
+
+    ///
+    /// Adds an empty sheet at the end of the file, returning its index.
+    ///
+    pub fn add_sheet(
+        &mut self,
+        name: ByteString,
+        column_types: Vec<ColumnType>,
+    ) -> Result<usize, EditMBEFileError> {
+        if self.sheets.iter().any(|x| x.name == name) {
+            return Err(EditMBEFileError::DuplicateSheetName(name));
+        }
+        Self::check_known_types(&column_types)?;
+        self.sheets.push(Sheet {
+            name,
+            column_types,
+            rows: Vec::new(),
+            padding: None,
+        });
+        self.invalidate_index();
+        Ok(self.sheets.len() - 1)
+    }
+
+    pub fn remove_sheet(&mut self, sheet: usize) -> Result<(), EditMBEFileError> {
+        self.check_sheet(sheet)?;
+        let removed = self.sheets.remove(sheet);
+        self.invalidate_index();
+        self.remove_strings(
+            &removed
+                .rows
+                .iter()
+                .flat_map(|row| &row.cells)
+                .filter_map(|x| x.string_index())
+                .collect(),
+        );
+        Ok(())
+    }
+
+    ///
+    /// Inserts a column at position `column`, setting it to `default` in every row.
+    ///
+    pub fn add_column(
+        &mut self,
+        sheet: usize,
+        column: usize,
+        default: TableCreateCell,
+    ) -> Result<(), EditMBEFileError> {
+        let current_sheet = self.check_sheet(sheet)?;
+        if current_sheet.column_types.len() < column {
+            return Err(EditMBEFileError::ColumnOutOfBounds { sheet, column });
+        }
+        Self::check_known_types(&current_sheet.column_types)?;
+
+        self.sheets[sheet]
+            .column_types
+            .insert(column, default.type_());
+        for row in 0..self.sheets[sheet].rows.len() {
+            let cell = self.store_cell(default.clone());
+            self.sheets[sheet].rows[row].cells.insert(column, cell);
+        }
+        self.forget_padding(sheet);
+        self.invalidate_index();
+        Ok(())
+    }
+
+    pub fn remove_column(&mut self, sheet: usize, column: usize) -> Result<(), EditMBEFileError> {
+        let current_sheet = self.check_sheet(sheet)?;
+        if current_sheet.column_types.len() <= column {
+            return Err(EditMBEFileError::ColumnOutOfBounds { sheet, column });
+        }
+        // Removing the last unknown column is fine, as there is nothing left to lose
+        let mut column_types = current_sheet.column_types.clone();
+        column_types.remove(column);
+        Self::check_known_types(&column_types)?;
+
+        let current_sheet = &mut self.sheets[sheet];
+        current_sheet.column_types.remove(column);
+        let removed = current_sheet
+            .rows
+            .iter_mut()
+            .filter_map(|row| row.cells.remove(column).string_index())
+            .collect::<HashSet<_>>();
+        self.remove_strings(&removed);
+        self.forget_padding(sheet);
+        self.invalidate_index();
+        Ok(())
+    }
+}
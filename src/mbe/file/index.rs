@@ -0,0 +1,97 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use byte_string::ByteString;
+
+use super::{MBEFile, RowId, RowSelectioner};
+
+///
+/// The ID of a row to look for, matched with the value of the first column of the rows.
+/// `Int` matches `Int` and `IntID` columns, and `String` matches `String` and `StringID`
+/// columns, an empty string matching the cells without content.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RowKey<'a> {
+    Int(u32),
+    String(&'a [u8]),
+}
+
+impl From<RowKey<'_>> for RowId {
+    fn from(value: RowKey<'_>) -> Self {
+        match value {
+            RowKey::Int(x) => Self::Int(x),
+            RowKey::String([]) => Self::String(None),
+            RowKey::String(x) => Self::String(Some(ByteString(x.to_vec()))),
+        }
+    }
+}
+
+///
+/// The position of each row of a file by ID, one map per sheet. It's built on the first
+/// lookup, and dropped whenever an ID may have changed.
+///
+#[derive(Debug, Clone, Default)]
+pub(super) struct RowIndex(OnceLock<Vec<HashMap<RowId, usize>>>);
+
+impl RowIndex {
+    pub(super) const fn new() -> Self {
+        Self(OnceLock::new())
+    }
+}
+
+/// The index is only a cache, so it doesn't take part in the comparison of files.
+impl PartialEq for RowIndex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl MBEFile {
+    fn row_index(&self) -> &[HashMap<RowId, usize>] {
+        self.index.0.get_or_init(|| {
+            self.sheets
+                .iter()
+                .map(|sheet| {
+                    let mut ids = HashMap::with_capacity(sheet.rows.len());
+                    for (position, row) in sheet.rows.iter().enumerate() {
+                        let id = match RowId::of(row, &self.data) {
+                            Some(RowId::String(Some(x))) if x.is_empty() => RowId::String(None),
+                            Some(id) => id,
+                            None => continue,
+                        };
+                        // The first row with an ID wins, like with a linear search
+                        ids.entry(id).or_insert(position);
+                    }
+                    ids
+                })
+                .collect()
+        })
+    }
+
+    /// Drops the index, after an edit that may have changed the IDs or the position of rows.
+    pub(super) fn invalidate_index(&mut self) {
+        self.index.0.take();
+    }
+
+    ///
+    /// Finds the first row whose ID is `id`, returning its sheet and its position in the
+    /// sheet. Sheets are searched in order.
+    ///
+    /// Lookups go through an index of every sheet, built by the first one.
+    ///
+    pub fn find_row_by_id(&self, id: RowKey) -> Option<(usize, usize)> {
+        let id = RowId::from(id);
+        self.row_index()
+            .iter()
+            .enumerate()
+            .find_map(|(sheet, ids)| Some((sheet, *ids.get(&id)?)))
+    }
+}
+
+impl RowSelectioner<'_> {
+    /// Finds the position of the first row of the sheet whose ID is `id`.
+    pub fn find_row_by_id(self, id: RowKey) -> Option<usize> {
+        self.file.row_index()[self.sheet_index]
+            .get(&RowId::from(id))
+            .copied()
+    }
+}
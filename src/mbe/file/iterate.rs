@@ -0,0 +1,177 @@
+use std::{iter::FusedIterator, ops::Range};
+
+use byte_string::ByteStr;
+
+use super::{MBEFile, PublicTableCell, RowSelectioner};
+
+///
+/// A row of an [`MBEFile`], with where it is in the file.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowRef<'a> {
+    pub sheet_index: usize,
+    pub sheet_name: &'a ByteStr,
+    pub row_index: usize,
+    pub cells: Vec<PublicTableCell<'a>>,
+}
+
+impl<'a> RowRef<'a> {
+    fn new(file: &'a MBEFile, sheet_index: usize, row_index: usize) -> Self {
+        let sheet = file.get_sheet_by_index(sheet_index).unwrap();
+        Self {
+            sheet_index,
+            sheet_name: sheet.name(),
+            row_index,
+            cells: sheet.get_row(row_index).unwrap().content(),
+        }
+    }
+}
+
+///
+/// The sheets of an [`MBEFile`], in order.
+///
+#[derive(Debug, Clone)]
+pub struct SheetIterator<'a> {
+    file: &'a MBEFile,
+    sheets: Range<usize>,
+}
+
+impl<'a> Iterator for SheetIterator<'a> {
+    type Item = RowSelectioner<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.sheets.next()?;
+        self.file.get_sheet_by_index(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.sheets.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for SheetIterator<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = self.sheets.next_back()?;
+        self.file.get_sheet_by_index(index)
+    }
+}
+
+impl ExactSizeIterator for SheetIterator<'_> {}
+impl FusedIterator for SheetIterator<'_> {}
+
+///
+/// The rows of a sheet, in order.
+///
+#[derive(Debug, Clone)]
+pub struct SheetRowIterator<'a> {
+    sheet: RowSelectioner<'a>,
+    rows: Range<usize>,
+}
+
+impl<'a> Iterator for SheetRowIterator<'a> {
+    type Item = RowRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next()?;
+        Some(RowRef::new(self.sheet.file, self.sheet.sheet_index, row))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for SheetRowIterator<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next_back()?;
+        Some(RowRef::new(self.sheet.file, self.sheet.sheet_index, row))
+    }
+}
+
+impl ExactSizeIterator for SheetRowIterator<'_> {}
+impl FusedIterator for SheetRowIterator<'_> {}
+
+///
+/// Every row of every sheet of an [`MBEFile`], in order.
+///
+#[derive(Debug, Clone)]
+pub struct RowIterator<'a> {
+    file: &'a MBEFile,
+    /// The sheet and the row of the next row from the front.
+    front: (usize, usize),
+    /// The sheet and the row following the next row from the back.
+    back: (usize, usize),
+    remaining: usize,
+}
+
+impl<'a> Iterator for RowIterator<'a> {
+    type Item = RowRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // Skips the sheets that are done, or that have no rows
+        while self.front.1 >= self.file.sheets[self.front.0].rows.len() {
+            self.front = (self.front.0 + 1, 0);
+        }
+        let (sheet, row) = self.front;
+        self.front.1 += 1;
+        self.remaining -= 1;
+        Some(RowRef::new(self.file, sheet, row))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for RowIterator<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.back.1 == 0 {
+            let sheet = self.back.0 - 1;
+            self.back = (sheet, self.file.sheets[sheet].rows.len());
+        }
+        self.back.1 -= 1;
+        self.remaining -= 1;
+        Some(RowRef::new(self.file, self.back.0, self.back.1))
+    }
+}
+
+impl ExactSizeIterator for RowIterator<'_> {}
+impl FusedIterator for RowIterator<'_> {}
+
+impl MBEFile {
+    pub fn sheets(&self) -> SheetIterator<'_> {
+        SheetIterator {
+            file: self,
+            sheets: 0..self.sheets.len(),
+        }
+    }
+
+    pub fn rows(&self) -> RowIterator<'_> {
+        RowIterator {
+            file: self,
+            front: (0, 0),
+            back: (self.sheets.len(), 0),
+            remaining: self.sheets.iter().map(|x| x.rows.len()).sum(),
+        }
+    }
+}
+
+impl<'a> RowSelectioner<'a> {
+    /// The index of the sheet in its file.
+    pub fn index(self) -> usize {
+        self.sheet_index
+    }
+
+    pub fn rows(self) -> SheetRowIterator<'a> {
+        SheetRowIterator {
+            sheet: self,
+            rows: 0..self.number_of_row(),
+        }
+    }
+}
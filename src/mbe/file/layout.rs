@@ -0,0 +1,529 @@
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read, Write},
+};
+
+use byte_string::ByteString;
+use byteorder::{LittleEndian, WriteBytesExt};
+use itertools::Itertools;
+
+use crate::helpers::{
+    binary::{BinaryLayout, binary_layout},
+    offset_wrapper::{OffsetReadWrapper, OffsetWriteWrapper},
+};
+
+use super::{ColumnType, FieldLayout, ParseMBEFileErrorKind, Row, TableCell, TableCreateCell};
+
+/// The byte the game fills the padding between fields with.
+const ROW_PADDING: u8 = 0xcc;
+
+/// The sizes tried for the fields of unknown types, the largest first.
+const GUESSED_SIZES: [usize; 4] = [8, 4, 2, 1];
+
+/// Past this number of unknown types in a sheet, their sizes aren't guessed.
+const MAX_GUESSED_TYPES: usize = 4;
+
+/// The length a size prefixed string of `length` bytes takes once padded to 4 bytes. There
+/// is always at least one byte of padding.
+pub(super) fn padded_string_length(length: usize) -> usize {
+    (length / 4 + 1) * 4
+}
+
+/// The length of a string read with its padding, without the trailing NULs.
+fn string_content_length(string: &[u8]) -> usize {
+    string.iter().rposition(|&x| x != 0).map_or(0, |x| x + 1)
+}
+
+///
+/// A string preceded by its length and followed by its padding, the length including the
+/// padding. The string is read without its trailing NULs, which are kept as its padding.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct PaddedString {
+    pub(super) string: ByteString,
+    pub(super) padding: Box<[u8]>,
+}
+
+impl PaddedString {
+    ///
+    /// `string` followed by `padding`, or if it's `None` by at least `nul_bytes` NULs and
+    /// then by NULs up to a multiple of 4 bytes.
+    ///
+    pub(super) fn new(string: &[u8], nul_bytes: usize, padding: Option<&[u8]>) -> Self {
+        let padding = padding.map_or_else(
+            || vec![0; padded_string_length(string.len() + nul_bytes) - string.len()].into(),
+            Box::from,
+        );
+        Self {
+            string: ByteString(string.to_vec()),
+            padding,
+        }
+    }
+
+    ///
+    /// Reads the string like [`BinaryLayout::read_from`], borrowing it from `source`
+    /// instead of copying it. The padding is skipped.
+    ///
+    pub(super) fn read_borrowed<'a>(source: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+        let length = u32::read_from(source)? as usize;
+        let Some((string, rest)) = source.split_at_checked(length) else {
+            // Consumes what is left, like `read_exact`
+            return source.read_exact(&mut vec![0; length]).map(|_| &[][..]);
+        };
+        *source = rest;
+        Ok(&string[..string_content_length(string)])
+    }
+}
+
+impl BinaryLayout for PaddedString {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self> {
+        let length = u32::read_from(source)? as usize;
+        let mut string = vec![0; length];
+        source.read_exact(&mut string)?;
+        let padding = string.split_off(string_content_length(&string));
+        Ok(Self {
+            string: ByteString(string),
+            padding: padding.into_boxed_slice(),
+        })
+    }
+
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()> {
+        ((self.string.len() + self.padding.len()) as u32).write_to(destination)?;
+        destination.write_all(&self.string)?;
+        destination.write_all(&self.padding)
+    }
+}
+
+impl BinaryLayout for ColumnType {
+    fn read_from(source: &mut dyn Read) -> io::Result<Self> {
+        Ok(Self::from_value(u32::read_from(source)?))
+    }
+
+    fn write_to(&self, destination: &mut dyn Write) -> io::Result<()> {
+        self.value().write_to(destination)
+    }
+}
+
+binary_layout! {
+    /// The start of an MBE file, followed by its sheets.
+    pub(super) struct ExpaHeader {
+        const b"EXPA",
+        pub(super) sheet_count: u32,
+    }
+}
+
+binary_layout! {
+    /// The start of a sheet, followed by its rows aligned to 8 bytes.
+    pub(super) struct SheetHeader {
+        pub(super) name: PaddedString,
+        pub(super) columns: SheetColumns,
+    }
+}
+
+binary_layout! {
+    ///
+    /// What follows the name of a sheet in its header. It's read on its own so that errors
+    /// can tell which sheet they come from.
+    ///
+    pub(super) struct SheetColumns {
+        pub(super) column_types: Vec<ColumnType>,
+        pub(super) row_length: u32,
+        pub(super) row_count: u32,
+    }
+}
+
+binary_layout! {
+    /// The start of the `CHNK` section, aligned to 8 bytes and followed by its entries.
+    pub(super) struct ChunkHeader {
+        const b"CHNK",
+        pub(super) entry_count: u32,
+    }
+}
+
+binary_layout! {
+    /// A string of the `CHNK` section, with the offset of the cell referencing it.
+    pub(super) struct ChunkEntry {
+        pub(super) offset: u32,
+        pub(super) string: PaddedString,
+    }
+}
+
+///
+/// Where everything of a sheet is placed in an `EXPA` section.
+///
+/// A sheet starts with a header (its name, its column types, the length of a row and the
+/// number of rows), followed by its rows, aligned to 8 bytes. Each field is aligned
+/// inside its row like in a C struct, and rows are padded so that the next one is aligned
+/// as well.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SheetLayout {
+    /// The size of the header, excluding the alignment before the rows.
+    header_size: usize,
+    /// The offset of each field relative to the start of its row.
+    field_offsets: Vec<usize>,
+    field_layouts: Vec<FieldLayout>,
+    /// Where the last field ends.
+    fields_end: usize,
+    /// The length of a row, padding included, which is also the distance between two rows.
+    row_length: usize,
+}
+
+///
+/// The bytes of a parsed sheet that aren't part of its content, so that it's written back
+/// as it was.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SheetPadding {
+    /// What follows the name, up to the length given by the file.
+    pub(super) name: Box<[u8]>,
+    /// The bytes aligning the rows to 8 bytes.
+    pub(super) rows_alignment: Box<[u8]>,
+    /// The length of a row given by the file.
+    pub(super) row_length: usize,
+    /// The layout of the unknown column types of the sheet, by value.
+    pub(super) unknown_layouts: HashMap<u32, FieldLayout>,
+}
+
+///
+/// The bytes of a parsed file that aren't part of its content.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct FilePadding {
+    /// The bytes aligning the `CHNK` section to 8 bytes.
+    pub(super) chunk_alignment: Box<[u8]>,
+    /// Whether the file had a `CHNK` section.
+    pub(super) has_chunk: bool,
+    /// Whatever followed the last `CHNK` entry, or the alignment if there was no `CHNK`
+    /// section.
+    pub(super) trailer: Vec<u8>,
+}
+
+/// The value of a field, as written in a row.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum RawField {
+    Int(u32),
+    Byte(u8),
+    Float(f32),
+    /// Strings are pointers, set by the game when loading the file.
+    String,
+    /// Fields of unknown types are kept as they were parsed.
+    Unknown,
+}
+
+impl From<&TableCell> for RawField {
+    fn from(value: &TableCell) -> Self {
+        match *value {
+            TableCell::Int(x) | TableCell::IntID(x) => Self::Int(x),
+            TableCell::Byte(x) => Self::Byte(x),
+            TableCell::Float(x) => Self::Float(x),
+            TableCell::String(_) | TableCell::StringID(_) => Self::String,
+            TableCell::Unknown { .. } => Self::Unknown,
+        }
+    }
+}
+
+impl From<&TableCreateCell<'_>> for RawField {
+    fn from(value: &TableCreateCell<'_>) -> Self {
+        match *value {
+            TableCreateCell::Int(x) | TableCreateCell::IntID(x) => Self::Int(x),
+            TableCreateCell::Byte(x) => Self::Byte(x),
+            TableCreateCell::Float(x) => Self::Float(x),
+            TableCreateCell::String(_) | TableCreateCell::StringID(_) => Self::String,
+        }
+    }
+}
+
+/// The layout of the fields of `type_`, the ones of unknown types being taken from
+/// `unknown_layouts`.
+fn field_layout(
+    type_: ColumnType,
+    unknown_layouts: &HashMap<u32, FieldLayout>,
+) -> Option<FieldLayout> {
+    match (type_.size(), type_.alignment()) {
+        (Some(size), Some(alignment)) => Some(FieldLayout {
+            size,
+            alignment: alignment as usize,
+        }),
+        _ => unknown_layouts.get(&type_.value()).copied(),
+    }
+}
+
+/// The offset of each field, and the length of a row holding them, like in a C struct.
+fn place_fields(layouts: &[FieldLayout]) -> (Vec<usize>, usize) {
+    let mut end = 0_usize;
+    let field_offsets = layouts
+        .iter()
+        .map(|layout| {
+            let offset = end.next_multiple_of(layout.alignment);
+            end = offset + layout.size;
+            offset
+        })
+        .collect();
+    let row_alignment = layouts.iter().map(|x| x.alignment).max().unwrap_or(1);
+    (field_offsets, end.next_multiple_of(row_alignment))
+}
+
+///
+/// Finds the layout of the unknown column types of a sheet whose rows are `row_length`
+/// bytes long. The types that aren't in `declared` are guessed: their fields are taken as
+/// large as possible while still fitting in the rows, and aligned to their size.
+///
+/// Returns the first column whose layout can't be found on errors.
+///
+pub(super) fn unknown_layouts(
+    column_types: &[ColumnType],
+    row_length: usize,
+    declared: &HashMap<u32, FieldLayout>,
+) -> Result<HashMap<u32, FieldLayout>, usize> {
+    let mut layouts = HashMap::new();
+    let mut guessed = Vec::new();
+    for &type_ in column_types {
+        if let ColumnType::Unknown(value) = type_ {
+            match declared.get(&value) {
+                Some(&layout) => {
+                    layouts.insert(value, layout);
+                }
+                None if !guessed.contains(&value) => guessed.push(value),
+                None => (),
+            }
+        }
+    }
+    if guessed.is_empty() {
+        return Ok(layouts);
+    }
+
+    let first_guessed = || {
+        column_types
+            .iter()
+            .position(|x| matches!(x, ColumnType::Unknown(x) if guessed.contains(x)))
+            .unwrap()
+    };
+    if guessed.len() > MAX_GUESSED_TYPES {
+        return Err(first_guessed());
+    }
+    guessed
+        .iter()
+        .map(|_| GUESSED_SIZES)
+        .multi_cartesian_product()
+        .map(|sizes| {
+            let mut layouts = layouts.clone();
+            layouts.extend(guessed.iter().zip(sizes).map(|(&value, size)| {
+                (
+                    value,
+                    FieldLayout {
+                        size,
+                        alignment: size,
+                    },
+                )
+            }));
+            layouts
+        })
+        .find(|layouts| {
+            let fields = column_types
+                .iter()
+                .map(|&x| field_layout(x, layouts).unwrap())
+                .collect::<Vec<_>>();
+            place_fields(&fields).1 <= row_length
+        })
+        .ok_or_else(first_guessed)
+}
+
+impl SheetLayout {
+    ///
+    /// The layout of a sheet, keeping the lengths given by `padding` if the sheet was
+    /// parsed.
+    ///
+    /// # Panics
+    ///
+    /// If the sheet has unknown column types that aren't in `padding`. These only come
+    /// from parsed sheets, which keep their layout.
+    ///
+    pub(super) fn new(
+        name: &[u8],
+        column_types: &[ColumnType],
+        padding: Option<&SheetPadding>,
+    ) -> Self {
+        let no_unknown_layouts = HashMap::new();
+        let unknown_layouts = padding.map_or(&no_unknown_layouts, |x| &x.unknown_layouts);
+        let name_length = match padding {
+            Some(padding) => name.len() + padding.name.len(),
+            None => padded_string_length(name.len()),
+        };
+        Self {
+            // name, number of columns, column types, row length and number of rows
+            header_size: 4 + name_length + 4 + 4 * column_types.len() + 8,
+            ..Self::of_rows(column_types, unknown_layouts, padding.map(|x| x.row_length))
+        }
+    }
+
+    ///
+    /// The layout of the rows of a sheet, for when its header isn't written. The length of
+    /// the rows is computed from their fields if `row_length` is `None`.
+    ///
+    /// # Panics
+    ///
+    /// If the sheet has unknown column types that aren't in `unknown_layouts`.
+    ///
+    pub(super) fn of_rows(
+        column_types: &[ColumnType],
+        unknown_layouts: &HashMap<u32, FieldLayout>,
+        row_length: Option<usize>,
+    ) -> Self {
+        let field_layouts = column_types
+            .iter()
+            .map(|&x| {
+                field_layout(x, unknown_layouts)
+                    .expect("unknown column types should only be found in parsed sheets")
+            })
+            .collect::<Vec<_>>();
+        let (field_offsets, natural_row_length) = place_fields(&field_layouts);
+        let fields_end = field_offsets
+            .last()
+            .zip(field_layouts.last())
+            .map_or(0, |(offset, layout)| offset + layout.size);
+
+        Self {
+            header_size: 0,
+            field_offsets,
+            field_layouts,
+            fields_end,
+            row_length: row_length.unwrap_or(natural_row_length),
+        }
+    }
+
+    /// The offset of a field relative to the start of its row.
+    pub(super) fn field_offset(&self, column: usize) -> usize {
+        self.field_offsets[column]
+    }
+
+    pub(super) fn field_layout(&self, column: usize) -> FieldLayout {
+        self.field_layouts[column]
+    }
+
+    pub(super) fn row_length(&self) -> usize {
+        self.row_length
+    }
+
+    /// Fails if the rows are too short to hold every field.
+    pub(super) fn check_row_length(&self) -> Result<(), ParseMBEFileErrorKind> {
+        if self.row_length < self.fields_end {
+            return Err(ParseMBEFileErrorKind::RowTooShort {
+                expected: self.fields_end,
+                found: self.row_length,
+            });
+        }
+        Ok(())
+    }
+
+    ///
+    /// Reads the rows of a sheet, `source` being at the first row. The length of the rows
+    /// must be able to hold every field.
+    ///
+    /// `current_row` is set to the row being read, and reset once they are all read.
+    ///
+    pub(super) fn read_rows(
+        &self,
+        source: &mut OffsetReadWrapper,
+        column_types: &[ColumnType],
+        row_number: usize,
+        current_row: &mut Option<usize>,
+    ) -> Result<Vec<Row>, ParseMBEFileErrorKind> {
+        self.check_row_length()?;
+
+        let rows = (0..row_number)
+            .map(|row| {
+                *current_row = Some(row);
+                let row_offset = source.offset();
+                let mut row_buffer = vec![0; self.row_length];
+                source.read_exact(&mut row_buffer)?;
+                let cells = column_types
+                    .iter()
+                    .zip(&self.field_offsets)
+                    .zip(&self.field_layouts)
+                    .map(|((&type_, &field_offset), &layout)| {
+                        let mut field = Cursor::new(&row_buffer[field_offset..]);
+                        TableCell::parse(
+                            (row_offset + field_offset) as u32,
+                            &mut field,
+                            type_,
+                            layout,
+                            field_offset,
+                        )
+                    })
+                    .collect::<io::Result<_>>()?;
+                Ok(Row {
+                    cells,
+                    raw: Some(row_buffer.into_boxed_slice()),
+                })
+            })
+            .collect::<Result<_, ParseMBEFileErrorKind>>()?;
+        *current_row = None;
+        Ok(rows)
+    }
+
+    pub(super) fn write_header(
+        &self,
+        writer: &mut OffsetWriteWrapper,
+        name: &[u8],
+        column_types: &[ColumnType],
+        row_number: usize,
+        padding: Option<&SheetPadding>,
+    ) -> io::Result<()> {
+        let header_offset = writer.offset();
+        SheetHeader {
+            name: PaddedString::new(name, 0, padding.map(|x| &*x.name)),
+            columns: SheetColumns {
+                column_types: column_types.to_vec(),
+                row_length: self.row_length as u32,
+                row_count: row_number as u32,
+            },
+        }
+        .write_to(writer)?;
+        debug_assert_eq!(writer.offset() - header_offset, self.header_size);
+        match padding {
+            Some(padding)
+                if padding.rows_alignment.len()
+                    == writer.offset().next_multiple_of(8) - writer.offset() =>
+            {
+                writer.write_all(&padding.rows_alignment)?;
+            }
+            _ => {
+                writer.align(8, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Writes a row and returns its offset. `fields` must follow the column types the
+    /// layout was made with. The padding is taken from `raw`, the bytes the row was parsed
+    /// from, if there are any.
+    ///
+    pub(super) fn write_row(
+        &self,
+        writer: &mut OffsetWriteWrapper,
+        fields: impl IntoIterator<Item = RawField>,
+        raw: Option<&[u8]>,
+    ) -> io::Result<usize> {
+        let (mut row, from_raw) = match raw {
+            Some(raw) if raw.len() == self.row_length => (raw.to_vec(), true),
+            _ => (vec![ROW_PADDING; self.row_length], false),
+        };
+        for (field, &offset) in fields.into_iter().zip(&self.field_offsets) {
+            let mut destination = &mut row[offset..];
+            match field {
+                RawField::Int(x) => destination.write_u32::<LittleEndian>(x)?,
+                RawField::Byte(x) => destination.write_u8(x)?,
+                RawField::Float(x) => destination.write_f32::<LittleEndian>(x)?,
+                // The pointers of parsed rows are kept, whatever their value
+                RawField::String | RawField::Unknown if from_raw => (),
+                RawField::String => destination.write_u64::<LittleEndian>(0)?,
+                RawField::Unknown => (),
+            }
+        }
+        let row_offset = writer.offset();
+        writer.write_all(&row)?;
+        Ok(row_offset)
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    sync::Arc,
+};
+
+use crate::{
+    PlaceholderOrCharacter,
+    mbe::{ColumnRole, SchemaRegistry},
+};
+
+use super::{MBEFile, Row, TableCell, cell_to_public};
+
+/// How [`TablePrinter`] lays out the sheets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableFormat {
+    /// Columns separated by `|` and padded to the same width.
+    #[default]
+    Text,
+    /// A Markdown table per sheet, under a heading.
+    Markdown,
+    /// An HTML table per sheet, under a heading.
+    Html,
+}
+
+///
+/// Prints the sheets of an [`MBEFile`] as tables, for a human to read.
+///
+/// The header of each table has the type of each column, with the name of the column in
+/// front of it if the sheet has a schema, as `<column name>:<column type>`.
+///
+pub struct TablePrinter {
+    format: TableFormat,
+    max_string_length: Option<usize>,
+    string_offsets: bool,
+    speaker_names: bool,
+    schemas: Option<Arc<SchemaRegistry>>,
+}
+
+impl Default for TablePrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TablePrinter {
+    pub const fn new() -> Self {
+        Self {
+            format: TableFormat::Text,
+            max_string_length: None,
+            string_offsets: false,
+            speaker_names: false,
+            schemas: None,
+        }
+    }
+
+    pub fn with_format(self, format: TableFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    ///
+    /// Sets how many characters of a string are printed, the rest being replaced by `…`.
+    /// Strings are printed whole if `None`, which is the default.
+    ///
+    pub fn with_max_string_length(self, max_string_length: Option<usize>) -> Self {
+        Self {
+            max_string_length,
+            ..self
+        }
+    }
+
+    ///
+    /// Sets whether strings are preceded by the offset of their `CHNK` entry, as it will be
+    /// once the file is written.
+    ///
+    pub fn with_string_offsets(self, string_offsets: bool) -> Self {
+        Self {
+            string_offsets,
+            ..self
+        }
+    }
+
+    ///
+    /// Sets whether the `Int` cells of the columns holding speakers are printed as the name
+    /// of the [`PlaceholderOrCharacter`] they stand for.
+    ///
+    pub fn with_speaker_names(self, speaker_names: bool) -> Self {
+        Self {
+            speaker_names,
+            ..self
+        }
+    }
+
+    ///
+    /// Sets the schemas naming the columns and telling which ones hold speakers. Defaults to
+    /// [`SchemaRegistry::default`].
+    ///
+    pub fn with_schemas(self, schemas: Option<Arc<SchemaRegistry>>) -> Self {
+        Self { schemas, ..self }
+    }
+
+    pub fn print(&self, file: &MBEFile, destination: &mut dyn Write) -> io::Result<()> {
+        let schemas = self.schemas.clone().unwrap_or_default();
+        let string_offsets = if self.string_offsets {
+            file.string_offsets()
+        } else {
+            Vec::new()
+        };
+
+        for (index, sheet) in file.sheets.iter().enumerate() {
+            let schema = schemas.find(&sheet.name, &sheet.column_types);
+            let header = sheet
+                .column_types
+                .iter()
+                .enumerate()
+                .map(
+                    |(column, x)| match schema.and_then(|x| x.column_name(column)) {
+                        Some(name) => format!("{name}:{}", x.name()),
+                        None => x.name().into_owned(),
+                    },
+                )
+                .collect::<Vec<_>>();
+            let speaker = schema
+                .filter(|_| self.speaker_names)
+                .and_then(|x| x.column(ColumnRole::Speaker));
+            let rows = sheet
+                .rows
+                .iter()
+                .map(|row| {
+                    row.cells
+                        .iter()
+                        .enumerate()
+                        .map(|(column, &cell)| {
+                            self.cell_to_string(file, row, cell, column, speaker, &string_offsets)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            if index > 0 {
+                writeln!(destination)?;
+            }
+            let name = String::from_utf8_lossy(&sheet.name);
+            match self.format {
+                TableFormat::Text => print_text(destination, index, &name, &header, &rows)?,
+                TableFormat::Markdown => print_markdown(destination, &name, &header, &rows)?,
+                TableFormat::Html => print_html(destination, &name, &header, &rows)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn cell_to_string(
+        &self,
+        file: &MBEFile,
+        row: &Row,
+        cell: TableCell,
+        column: usize,
+        speaker: Option<usize>,
+        string_offsets: &[Option<u32>],
+    ) -> String {
+        match cell {
+            TableCell::Int(x) if speaker == Some(column) => {
+                PlaceholderOrCharacter::from(x).name().into_owned()
+            }
+            TableCell::String(Some(x)) | TableCell::StringID(Some(x)) => {
+                let string = String::from_utf8_lossy(&file.data[x as usize].string);
+                let string = match self.max_string_length {
+                    Some(max) if string.chars().count() > max => {
+                        Cow::Owned(string.chars().take(max).chain(['…']).collect())
+                    }
+                    _ => string,
+                };
+                match string_offsets.get(x as usize) {
+                    Some(Some(offset)) => format!("[{offset:#x}] {string}"),
+                    _ => string.into_owned(),
+                }
+            }
+            cell => cell_to_public(cell, row.raw.as_deref(), &file.data).to_string(),
+        }
+    }
+}
+
+/// The width of a cell once printed, counting characters.
+fn width(cell: &str) -> usize {
+    cell.chars().count()
+}
+
+/// The width of each column, so that every cell fits.
+fn column_widths(header: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths = header.iter().map(|x| width(x)).collect::<Vec<_>>();
+    for row in rows {
+        for (width_, cell) in widths.iter_mut().zip(row) {
+            *width_ = (*width_).max(width(cell));
+        }
+    }
+    widths
+}
+
+fn write_padded_row(
+    destination: &mut dyn Write,
+    cells: impl Iterator<Item = String>,
+    widths: &[usize],
+    (start, separator, end): (&str, &str, &str),
+) -> io::Result<()> {
+    let cells = cells
+        .zip(widths)
+        .map(|(cell, &width_)| format!("{cell}{}", " ".repeat(width_ - width(&cell))))
+        .collect::<Vec<_>>();
+    writeln!(
+        destination,
+        "{}",
+        format!("{start}{}{end}", cells.join(separator)).trim_end()
+    )
+}
+
+fn print_text(
+    destination: &mut dyn Write,
+    index: usize,
+    name: &str,
+    header: &[String],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    let escape = |x: &String| x.replace('\n', "\\n");
+    let header = header.iter().map(escape).collect::<Vec<_>>();
+    let rows = rows
+        .iter()
+        .map(|row| row.iter().map(escape).collect())
+        .collect::<Vec<_>>();
+    let widths = column_widths(&header, &rows);
+
+    writeln!(destination, "sheet {index}: {name}")?;
+    write_padded_row(destination, header.into_iter(), &widths, ("", " | ", ""))?;
+    let rule = widths.iter().map(|&x| "-".repeat(x)).collect::<Vec<_>>();
+    writeln!(destination, "{}", rule.join("-+-"))?;
+    for row in rows {
+        write_padded_row(destination, row.into_iter(), &widths, ("", " | ", ""))?;
+    }
+    Ok(())
+}
+
+fn print_markdown(
+    destination: &mut dyn Write,
+    name: &str,
+    header: &[String],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    let escape = |x: &String| x.replace('|', "\\|").replace('\n', "<br>");
+    let header = header.iter().map(escape).collect::<Vec<_>>();
+    let rows = rows
+        .iter()
+        .map(|row| row.iter().map(escape).collect())
+        .collect::<Vec<_>>();
+    // The delimiter row needs at least 3 dashes
+    let widths = column_widths(&header, &rows)
+        .into_iter()
+        .map(|x| x.max(3))
+        .collect::<Vec<_>>();
+
+    writeln!(destination, "## {name}")?;
+    writeln!(destination)?;
+    write_padded_row(
+        destination,
+        header.into_iter(),
+        &widths,
+        ("| ", " | ", " |"),
+    )?;
+    let rule = widths.iter().map(|&x| "-".repeat(x)).collect::<Vec<_>>();
+    writeln!(destination, "| {} |", rule.join(" | "))?;
+    for row in rows {
+        write_padded_row(destination, row.into_iter(), &widths, ("| ", " | ", " |"))?;
+    }
+    Ok(())
+}
+
+fn escape_html(x: &str) -> String {
+    x.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+fn print_html(
+    destination: &mut dyn Write,
+    name: &str,
+    header: &[String],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    writeln!(destination, "<h2>{}</h2>", escape_html(name))?;
+    writeln!(destination, "<table>")?;
+    writeln!(destination, "  <thead>")?;
+    writeln!(destination, "    <tr>")?;
+    for cell in header {
+        writeln!(destination, "      <th>{}</th>", escape_html(cell))?;
+    }
+    writeln!(destination, "    </tr>")?;
+    writeln!(destination, "  </thead>")?;
+    writeln!(destination, "  <tbody>")?;
+    for row in rows {
+        writeln!(destination, "    <tr>")?;
+        for cell in row {
+            writeln!(destination, "      <td>{}</td>", escape_html(cell))?;
+        }
+        writeln!(destination, "    </tr>")?;
+    }
+    writeln!(destination, "  </tbody>")?;
+    writeln!(destination, "</table>")
+}
@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+
+use byte_string::ByteString;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use super::{ColumnType, MBEFile, PublicTableCell, RowSelectioner, TableCreateCell};
+
+///
+/// A string as serialized: UTF-8 strings are kept as is, and the others are written as
+/// their bytes. String cells without a `CHNK` entry are serialized as `null`, and told
+/// apart from empty strings.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerdeString {
+    Utf8(String),
+    Bytes { bytes: Vec<u8> },
+}
+
+impl From<&[u8]> for SerdeString {
+    fn from(value: &[u8]) -> Self {
+        match std::str::from_utf8(value) {
+            Ok(x) => Self::Utf8(x.to_string()),
+            Err(_) => Self::Bytes {
+                bytes: value.to_vec(),
+            },
+        }
+    }
+}
+
+impl SerdeString {
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Utf8(x) => x.is_empty(),
+            Self::Bytes { bytes } => bytes.is_empty(),
+        }
+    }
+}
+
+impl From<SerdeString> for Vec<u8> {
+    fn from(value: SerdeString) -> Self {
+        match value {
+            SerdeString::Utf8(x) => x.into_bytes(),
+            SerdeString::Bytes { bytes } => bytes,
+        }
+    }
+}
+
+///
+/// A float as serialized. Floats are written as numbers when these are parsed back to the
+/// same value, and as their bits in hexadecimal otherwise (NaNs and infinities).
+///
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerdeFloat {
+    Number(f64),
+    Bits(String),
+}
+
+impl From<f32> for SerdeFloat {
+    fn from(value: f32) -> Self {
+        if value.is_finite() {
+            // The shortest representation of the float, unless it's rounded to a different
+            // float when parsed as a double
+            let shortest = value.to_string().parse::<f64>().unwrap();
+            Self::Number(if shortest as f32 == value {
+                shortest
+            } else {
+                value.into()
+            })
+        } else {
+            Self::Bits(format!("{:#010x}", value.to_bits()))
+        }
+    }
+}
+
+impl TryFrom<SerdeFloat> for f32 {
+    type Error = String;
+
+    fn try_from(value: SerdeFloat) -> Result<Self, Self::Error> {
+        match value {
+            SerdeFloat::Number(x) => Ok(x as f32),
+            SerdeFloat::Bits(x) => x
+                .strip_prefix("0x")
+                .and_then(|bits| u32::from_str_radix(bits, 16).ok())
+                .map(f32::from_bits)
+                .ok_or_else(|| format!("{x:?} isn't a float")),
+        }
+    }
+}
+
+/// The serialized form of [`PublicTableCell`] and [`TableCreateCell`].
+#[derive(Serialize, Deserialize)]
+enum SerdeCell {
+    Int(u32),
+    IntID(u32),
+    Byte(u8),
+    Float(SerdeFloat),
+    String(Option<SerdeString>),
+    StringID(Option<SerdeString>),
+    /// Only serialized, as cells of unknown types can't be created.
+    Unknown(Vec<u8>),
+}
+
+impl From<PublicTableCell<'_>> for SerdeCell {
+    fn from(value: PublicTableCell<'_>) -> Self {
+        match value {
+            PublicTableCell::Int(x) => Self::Int(x),
+            PublicTableCell::IntID(x) => Self::IntID(x),
+            PublicTableCell::Byte(x) => Self::Byte(x),
+            PublicTableCell::Float(x) => Self::Float(x.into()),
+            PublicTableCell::String(x) => Self::String(x.map(|x| SerdeString::from(&x.0))),
+            PublicTableCell::StringID(x) => Self::StringID(x.map(|x| SerdeString::from(&x.0))),
+            PublicTableCell::Unknown(_, x) => Self::Unknown(x.to_vec()),
+        }
+    }
+}
+
+/// Empty strings are serialized as `null`, as they don't get a `CHNK` entry when inserted.
+impl From<&TableCreateCell<'_>> for SerdeCell {
+    fn from(value: &TableCreateCell<'_>) -> Self {
+        let string = |x: &[u8]| (!x.is_empty()).then(|| x.into());
+        match value {
+            &TableCreateCell::Int(x) => Self::Int(x),
+            &TableCreateCell::IntID(x) => Self::IntID(x),
+            &TableCreateCell::Byte(x) => Self::Byte(x),
+            &TableCreateCell::Float(x) => Self::Float(x.into()),
+            TableCreateCell::String(x) => Self::String(string(x)),
+            TableCreateCell::StringID(x) => Self::StringID(string(x)),
+        }
+    }
+}
+
+impl TryFrom<SerdeCell> for TableCreateCell<'static> {
+    type Error = String;
+
+    fn try_from(value: SerdeCell) -> Result<Self, Self::Error> {
+        let string = |x: Option<SerdeString>| Cow::Owned(x.map(Vec::from).unwrap_or_default());
+        Ok(match value {
+            SerdeCell::Int(x) => Self::Int(x),
+            SerdeCell::IntID(x) => Self::IntID(x),
+            SerdeCell::Byte(x) => Self::Byte(x),
+            SerdeCell::Float(x) => Self::Float(x.try_into()?),
+            SerdeCell::String(x) => Self::String(string(x)),
+            SerdeCell::StringID(x) => Self::StringID(string(x)),
+            SerdeCell::Unknown(_) => return Err("cells of unknown types can't be created".into()),
+        })
+    }
+}
+
+impl Serialize for PublicTableCell<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeCell::from(*self).serialize(serializer)
+    }
+}
+
+impl Serialize for TableCreateCell<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeCell::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TableCreateCell<'static> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SerdeCell::deserialize(deserializer)?
+            .try_into()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerdeSheet {
+    name: SerdeString,
+    column_types: Vec<ColumnType>,
+    rows: Vec<Vec<SerdeCell>>,
+}
+
+impl From<RowSelectioner<'_>> for SerdeSheet {
+    fn from(sheet: RowSelectioner<'_>) -> Self {
+        Self {
+            name: SerdeString::from(&sheet.name().0),
+            column_types: sheet.column_types().to_vec(),
+            rows: (0..sheet.number_of_row())
+                .flat_map(|x| sheet.get_row(x))
+                .map(|row| row.content().into_iter().map(SerdeCell::from).collect())
+                .collect(),
+        }
+    }
+}
+
+impl SerdeSheet {
+    ///
+    /// Adds the sheet at the end of `file`, returning its index. Empty strings get a `CHNK`
+    /// entry, unlike the strings serialized as `null`.
+    ///
+    fn add_to(self, file: &mut MBEFile) -> Result<usize, String> {
+        let index = file
+            .add_sheet(ByteString(self.name.into()), self.column_types)
+            .map_err(|x| x.to_string())?;
+        for (row, cells) in self.rows.into_iter().enumerate() {
+            let empty_strings = cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| match cell {
+                    SerdeCell::String(Some(x)) | SerdeCell::StringID(Some(x)) => x.is_empty(),
+                    _ => false,
+                })
+                .map(|(column, _)| column)
+                .collect::<Vec<_>>();
+            let cells = cells
+                .into_iter()
+                .map(TableCreateCell::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            file.insert_row(index, row, cells)
+                .map_err(|x| format!("sheet {index}, row {row}: {x}"))?;
+            for column in empty_strings {
+                file.set_empty_string(index, row, column)
+                    .map_err(|x| format!("sheet {index}, row {row}: {x}"))?;
+            }
+        }
+        Ok(index)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerdeFile {
+    sheets: Vec<SerdeSheet>,
+}
+
+/// A sheet is serialized with its name, the types of its columns and its rows.
+impl Serialize for RowSelectioner<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeSheet::from(*self).serialize(serializer)
+    }
+}
+
+impl Serialize for MBEFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sheets = (0..self.number_of_sheets())
+            .flat_map(|x| self.get_sheet_by_index(x))
+            .map(SerdeSheet::from)
+            .collect();
+        SerdeFile { sheets }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MBEFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut file = MBEFile::new();
+        for sheet in SerdeFile::deserialize(deserializer)?.sheets {
+            sheet.add_to(&mut file).map_err(D::Error::custom)?;
+        }
+        Ok(file)
+    }
+}
+
+impl RowSelectioner<'_> {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl MBEFile {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(source: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(source)
+    }
+
+    ///
+    /// Adds a sheet written by [`RowSelectioner::to_json`] at the end of the file,
+    /// returning its index. The file is left untouched if the sheet can't be added.
+    ///
+    pub fn add_sheet_from_json(&mut self, source: &str) -> serde_json::Result<usize> {
+        let sheet: SerdeSheet = serde_json::from_str(source)?;
+        let mut file = self.clone();
+        let index = sheet.add_to(&mut file).map_err(serde_json::Error::custom)?;
+        *self = file;
+        Ok(index)
+    }
+
+    pub fn to_yaml(&self) -> serde_norway::Result<String> {
+        serde_norway::to_string(self)
+    }
+
+    pub fn from_yaml(source: &str) -> serde_norway::Result<Self> {
+        serde_norway::from_str(source)
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::mbe::SchemaRegistry;
+
+use super::{MBEFile, RowId, TableCell, layout::SheetLayout};
+
+///
+/// Something wrong with the structure of an MBE file, found by [`MBEFile::validate`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// A row has the same ID as a previous row of its sheet.
+    DuplicateId {
+        sheet: usize,
+        row: usize,
+        first_row: usize,
+    },
+    /// A row has an ID lower than the one of the previous row.
+    UnsortedId { sheet: usize, row: usize },
+    /// An entry of the `CHNK` section isn't referenced by any string cell.
+    OrphanString { index: usize, offset: u32 },
+    /// A string cell has no entry in the `CHNK` section.
+    MissingString {
+        sheet: usize,
+        row: usize,
+        column: usize,
+    },
+    /// The length of the rows given by the file isn't the one of its columns.
+    RowLengthMismatch {
+        sheet: usize,
+        declared: usize,
+        expected: usize,
+    },
+    NonUtf8String {
+        sheet: usize,
+        row: usize,
+        column: usize,
+    },
+    /// No schema of the registry describes the sheet.
+    UnknownSheet { sheet: usize, name: String },
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateId {
+                sheet,
+                row,
+                first_row,
+            } => write!(f, "sheet {sheet}, row {row}: same ID as row {first_row}"),
+            Self::UnsortedId { sheet, row } => write!(
+                f,
+                "sheet {sheet}, row {row}: ID lower than the one of the previous row"
+            ),
+            Self::OrphanString { index, offset } => write!(
+                f,
+                "CHNK entry {index} points at {offset:#x}, which isn't a string cell"
+            ),
+            Self::MissingString { sheet, row, column } => write!(
+                f,
+                "sheet {sheet}, row {row}, column {column}: string without CHNK entry"
+            ),
+            Self::RowLengthMismatch {
+                sheet,
+                declared,
+                expected,
+            } => write!(
+                f,
+                "sheet {sheet}: rows are {declared} bytes long instead of {expected}"
+            ),
+            Self::NonUtf8String { sheet, row, column } => write!(
+                f,
+                "sheet {sheet}, row {row}, column {column}: string isn't valid UTF-8"
+            ),
+            Self::UnknownSheet { sheet, name } => {
+                write!(f, "sheet {sheet}: no schema describes {name:?}")
+            }
+        }
+    }
+}
+
+impl MBEFile {
+    ///
+    /// Checks the structure of the file, against the [default](SchemaRegistry::default)
+    /// schemas. See
+    /// [`MBEFile::validate_with_schemas`].
+    ///
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.validate_with_schemas(&SchemaRegistry::default())
+    }
+
+    ///
+    /// Checks the structure of the file, returning every issue found, sheet by sheet and
+    /// then for the `CHNK` section:
+    ///
+    /// - rows whose ID is the same as a previous row, or lower than the one of the previous
+    ///   row. IDs are the values of the first columns, like with [`MBEFile::patch`],
+    /// - `CHNK` entries that aren't referenced by a string cell,
+    /// - string cells without `CHNK` entry,
+    /// - rows whose length, as given by the file, isn't the one of their columns,
+    /// - strings that aren't valid UTF-8,
+    /// - sheets that aren't described by `schemas`.
+    ///
+    /// Files written by the game may have some of these issues already.
+    ///
+    pub fn validate_with_schemas(&self, schemas: &SchemaRegistry) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut referenced = HashSet::new();
+
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            if schemas.find(&sheet.name, &sheet.column_types).is_none() {
+                issues.push(ValidationIssue::UnknownSheet {
+                    sheet: index,
+                    name: String::from_utf8_lossy(&sheet.name).into_owned(),
+                });
+            }
+
+            if let Some(padding) = &sheet.padding {
+                let expected =
+                    SheetLayout::of_rows(&sheet.column_types, &padding.unknown_layouts, None)
+                        .row_length();
+                if padding.row_length != expected {
+                    issues.push(ValidationIssue::RowLengthMismatch {
+                        sheet: index,
+                        declared: padding.row_length,
+                        expected,
+                    });
+                }
+            }
+
+            let mut first_rows = HashMap::new();
+            let mut previous_id = None;
+            for (row_index, row) in sheet.rows.iter().enumerate() {
+                if let Some(id) = RowId::of(row, &self.data).map(|id| match id {
+                    RowId::String(Some(x)) if x.is_empty() => RowId::String(None),
+                    id => id,
+                }) {
+                    if previous_id.as_ref().is_some_and(|previous| id < *previous) {
+                        issues.push(ValidationIssue::UnsortedId {
+                            sheet: index,
+                            row: row_index,
+                        });
+                    }
+                    match first_rows.get(&id) {
+                        Some(&first_row) => issues.push(ValidationIssue::DuplicateId {
+                            sheet: index,
+                            row: row_index,
+                            first_row,
+                        }),
+                        None => {
+                            first_rows.insert(id.clone(), row_index);
+                        }
+                    }
+                    previous_id = Some(id);
+                }
+
+                for (column, cell) in row.cells.iter().enumerate() {
+                    let (TableCell::String(string) | TableCell::StringID(string)) = *cell else {
+                        continue;
+                    };
+                    let Some(string) = string else {
+                        issues.push(ValidationIssue::MissingString {
+                            sheet: index,
+                            row: row_index,
+                            column,
+                        });
+                        continue;
+                    };
+                    referenced.insert(string);
+                    if std::str::from_utf8(&self.data[string as usize].string).is_err() {
+                        issues.push(ValidationIssue::NonUtf8String {
+                            sheet: index,
+                            row: row_index,
+                            column,
+                        });
+                    }
+                }
+            }
+        }
+
+        issues.extend(
+            self.data
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| !referenced.contains(&(index as u32)))
+                .map(|(index, entry)| ValidationIssue::OrphanString {
+                    index,
+                    offset: entry.offset,
+                }),
+        );
+        issues
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashMap, io};
+
+use byte_string::ByteStr;
+
+use crate::helpers::binary::BinaryLayout;
+
+use super::{
+    ColumnType, FieldLayout, ParseContext, ParseMBEFileError, ParseMBEFileErrorKind,
+    PublicTableCell,
+    layout::{self, ChunkHeader, ExpaHeader, PaddedString, SheetColumns, SheetLayout},
+};
+
+///
+/// Reads the parts of an MBE file from a slice, without copying them. What is left to read
+/// is itself a reader, which the layouts of the headers are read from.
+///
+struct SliceReader<'a> {
+    bytes: &'a [u8],
+    rest: &'a [u8],
+}
+
+impl<'a> SliceReader<'a> {
+    fn offset(&self) -> usize {
+        self.bytes.len() - self.rest.len()
+    }
+
+    /// Skips the padding up to the next multiple of `alignment`, or to the end.
+    fn align(&mut self, alignment: usize) {
+        let offset = self
+            .offset()
+            .next_multiple_of(alignment)
+            .min(self.bytes.len());
+        self.rest = &self.bytes[offset..];
+    }
+}
+
+/// A sheet of an [`MBEView`], whose rows are decoded on demand.
+#[derive(Debug, Clone)]
+struct SheetData<'a> {
+    name: &'a ByteStr,
+    column_types: Vec<ColumnType>,
+    layout: SheetLayout,
+    /// The offset of the first row in the file.
+    rows_offset: usize,
+    rows: &'a [u8],
+    row_number: usize,
+}
+
+///
+/// A read-only MBE file, borrowing its content from the bytes it was parsed from.
+///
+/// Only the headers of the sheets and the `CHNK` section are read when parsing. Rows are
+/// decoded when they are accessed, and strings point into the parsed bytes. Use
+/// [`MBEFile`](super::MBEFile) to edit a file.
+///
+#[derive(Debug, Clone)]
+pub struct MBEView<'a> {
+    sheets: Vec<SheetData<'a>>,
+    /// The content of the `CHNK` section, by offset of the cell referencing it.
+    strings: HashMap<u32, &'a ByteStr>,
+}
+
+impl<'a> MBEView<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, ParseMBEFileError> {
+        Self::parse_with_unknown_layouts(bytes, &HashMap::new())
+    }
+
+    /// Like [`MBEView::parse`], with the layout of column types this crate doesn't know.
+    pub fn parse_with_unknown_layouts(
+        bytes: &'a [u8],
+        unknown_layouts: &HashMap<u32, FieldLayout>,
+    ) -> Result<Self, ParseMBEFileError> {
+        let mut source = SliceReader { bytes, rest: bytes };
+        let mut context = ParseContext::default();
+        Self::parse_in_context(&mut source, unknown_layouts, &mut context)
+            .map_err(|kind| context.error(kind, source.offset()))
+    }
+
+    fn parse_in_context(
+        source: &mut SliceReader<'a>,
+        unknown_layouts: &HashMap<u32, FieldLayout>,
+        context: &mut ParseContext,
+    ) -> Result<Self, ParseMBEFileErrorKind> {
+        let header = ExpaHeader::read_from(&mut source.rest)?;
+        let sheets = (0..header.sheet_count as usize)
+            .map(|sheet| {
+                context.sheet = Some(sheet);
+                context.sheet_name = None;
+                let name = ByteStr::new(PaddedString::read_borrowed(&mut source.rest)?);
+                context.sheet_name = Some(String::from_utf8_lossy(name).into_owned());
+                let SheetColumns {
+                    column_types,
+                    row_length,
+                    row_count,
+                } = SheetColumns::read_from(&mut source.rest)?;
+                let (row_length, row_number) = (row_length as usize, row_count as usize);
+                let unknown_layouts =
+                    layout::unknown_layouts(&column_types, row_length, unknown_layouts).map_err(
+                        |column| ParseMBEFileErrorKind::InvalidColumnType {
+                            column,
+                            value: column_types[column].value(),
+                        },
+                    )?;
+                source.align(8);
+
+                let layout =
+                    SheetLayout::of_rows(&column_types, &unknown_layouts, Some(row_length));
+                layout.check_row_length()?;
+                let rows_offset = source.offset();
+                // The rows are all there, or the error points to the first incomplete one
+                let available = match row_length {
+                    0 => row_number,
+                    _ => source.rest.len() / row_length,
+                };
+                if available < row_number {
+                    context.row = Some(available);
+                    source.rest = &source.rest[available * row_length..];
+                    // Fails like reading the incomplete row
+                    let mut row = vec![0; row_length];
+                    return Err(io::Read::read_exact(&mut source.rest, &mut row)
+                        .unwrap_err()
+                        .into());
+                }
+                let (rows, rest) = source.rest.split_at(row_number * row_length);
+                source.rest = rest;
+                Ok(SheetData {
+                    name,
+                    column_types,
+                    layout,
+                    rows_offset,
+                    rows,
+                    row_number,
+                })
+            })
+            .collect::<Result<Vec<_>, ParseMBEFileErrorKind>>()?;
+        context.sheet = None;
+        context.sheet_name = None;
+
+        source.align(8);
+        let mut strings = HashMap::new();
+        if source.rest.len() < 4 {
+            // No `CHNK` section
+            return Ok(Self { sheets, strings });
+        }
+        let header = ChunkHeader::read_from(&mut source.rest)?;
+        strings.reserve(header.entry_count as usize);
+        for _ in 0..header.entry_count {
+            let offset = u32::read_from(&mut source.rest)?;
+            let string = PaddedString::read_borrowed(&mut source.rest)?;
+            strings.insert(offset, ByteStr::new(string));
+        }
+
+        Ok(Self { sheets, strings })
+    }
+
+    pub fn number_of_sheets(&self) -> usize {
+        self.sheets.len()
+    }
+
+    pub fn get_sheet_by_index(&self, index: usize) -> Option<SheetView<'_, 'a>> {
+        (index < self.sheets.len()).then_some(SheetView { view: self, index })
+    }
+
+    pub fn get_sheet_by_name(&self, name: &[u8]) -> Option<SheetView<'_, 'a>> {
+        let index = self.sheets.iter().position(|x| x.name.0 == *name)?;
+        Some(SheetView { view: self, index })
+    }
+
+    /// Every row of every sheet, in order.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<PublicTableCell<'a>>> + '_ {
+        (0..self.sheets.len())
+            .flat_map(|x| self.get_sheet_by_index(x))
+            .flat_map(|sheet| (0..sheet.number_of_row()).flat_map(move |x| sheet.get_row(x)))
+            .map(|row| row.content())
+    }
+
+    /// Every string of the `CHNK` section, in no particular order.
+    pub fn strings(&self) -> impl Iterator<Item = &'a ByteStr> + '_ {
+        self.strings.values().copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SheetView<'v, 'a> {
+    view: &'v MBEView<'a>,
+    index: usize,
+}
+
+impl<'v, 'a> SheetView<'v, 'a> {
+    fn data(self) -> &'v SheetData<'a> {
+        &self.view.sheets[self.index]
+    }
+
+    pub fn name(self) -> &'a ByteStr {
+        self.data().name
+    }
+
+    pub fn column_types(self) -> &'v [ColumnType] {
+        &self.data().column_types
+    }
+
+    pub fn number_of_row(self) -> usize {
+        self.data().row_number
+    }
+
+    pub fn get_row(self, index: usize) -> Option<RowView<'v, 'a>> {
+        (index < self.number_of_row()).then_some(RowView { sheet: self, index })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RowView<'v, 'a> {
+    sheet: SheetView<'v, 'a>,
+    index: usize,
+}
+
+impl<'a> RowView<'_, 'a> {
+    pub fn content(self) -> Vec<PublicTableCell<'a>> {
+        (0..self.sheet.column_types().len())
+            .flat_map(|x| self.get_column(x))
+            .collect()
+    }
+
+    pub fn get_column(self, index: usize) -> Option<PublicTableCell<'a>> {
+        let data = self.sheet.data();
+        let type_ = *data.column_types.get(index)?;
+        let row_length = data.layout.row_length();
+        let row_offset = self.index * row_length;
+        let field_offset = data.layout.field_offset(index);
+        let FieldLayout { size, .. } = data.layout.field_layout(index);
+        let field = &data.rows[row_offset + field_offset..][..size];
+        let u32_field = || u32::from_le_bytes(field.try_into().unwrap());
+        let string = || {
+            let offset = data.rows_offset + row_offset + field_offset;
+            self.sheet.view.strings.get(&(offset as u32)).copied()
+        };
+        Some(match type_ {
+            ColumnType::Int => PublicTableCell::Int(u32_field()),
+            ColumnType::IntID => PublicTableCell::IntID(u32_field()),
+            ColumnType::Byte => PublicTableCell::Byte(field[0]),
+            ColumnType::Float => PublicTableCell::Float(f32::from_bits(u32_field())),
+            ColumnType::String => PublicTableCell::String(string()),
+            ColumnType::StringID => PublicTableCell::StringID(string()),
+            ColumnType::Unknown(value) => PublicTableCell::Unknown(value, field),
+        })
+    }
+}
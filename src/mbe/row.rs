@@ -0,0 +1,410 @@
+use std::{borrow::Cow, fmt::Display};
+
+use byte_string::ByteString;
+
+use crate::PlaceholderOrCharacter;
+
+use super::{ColumnType, EditMBEFileError, MBEFile, RowSelectioner, TableCell, TableCreateCell};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowMappingError {
+    SheetNotFound(String),
+    /// The type, whose name is given, isn't mapped to a sheet by [`FromMbeRow::SHEET`].
+    NoSheetName(&'static str),
+    WrongNumberOfColumns {
+        expected: usize,
+        found: usize,
+    },
+    /// The column a field is mapped to can't be read into or written from that field.
+    ColumnTypeMismatch {
+        field: &'static str,
+        column: usize,
+        found: ColumnType,
+    },
+    /// The cell has the right type, but its value can't be held by the field.
+    InvalidValue {
+        field: &'static str,
+        column: usize,
+    },
+    Edit(EditMBEFileError),
+}
+
+impl Display for RowMappingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SheetNotFound(sheet) => write!(f, "there is no sheet named {sheet}"),
+            Self::NoSheetName(type_) => write!(
+                f,
+                "{type_} isn't mapped to a sheet, which is named with #[mbe(sheet = \"...\")]"
+            ),
+            Self::WrongNumberOfColumns { expected, found } => {
+                write!(f, "expected {expected} columns, found {found}")
+            }
+            Self::ColumnTypeMismatch {
+                field,
+                column,
+                found,
+            } => write!(
+                f,
+                "field {field} can't be mapped to column {column}, which has type {found:?}"
+            ),
+            Self::InvalidValue { field, column } => {
+                write!(
+                    f,
+                    "the value of column {column} doesn't fit in field {field}"
+                )
+            }
+            Self::Edit(x) => write!(f, "{x}"),
+        }
+    }
+}
+impl std::error::Error for RowMappingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Edit(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<EditMBEFileError> for RowMappingError {
+    fn from(value: EditMBEFileError) -> Self {
+        Self::Edit(value)
+    }
+}
+
+///
+/// A value that can be read from a cell.
+///
+pub trait FromMbeCell: Sized {
+    /// Whether the value can be read from cells of `column_type`.
+    fn accepts(column_type: ColumnType) -> bool;
+    /// Reads the value, returning `None` if it doesn't fit.
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self>;
+}
+
+///
+/// A value that can be written to a cell.
+///
+pub trait ToMbeCell {
+    /// Converts the value to a cell of `column_type`, returning `None` if it can't be.
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>>;
+}
+
+///
+/// A type a row can be read into, usually implemented with `#[derive(FromMbeRow)]`.
+///
+/// ```ignore
+/// #[derive(FromMbeRow, ToMbeRow)]
+/// #[mbe(sheet = "message")]
+/// struct Line {
+///     id: u32,
+///     speaker: PlaceholderOrCharacter,
+///     text: String,
+/// }
+/// ```
+///
+/// Fields are mapped to the columns in order, unless they are given one with
+/// `#[mbe(column = 2)]`.
+///
+pub trait FromMbeRow: Sized {
+    /// The name of the sheet holding these rows, if there is one.
+    const SHEET: Option<&'static str>;
+
+    /// Checks that the rows of a sheet with these column types can be read.
+    fn check_column_types(column_types: &[ColumnType]) -> Result<(), RowMappingError>;
+
+    /// Reads a row, which must have been checked with [`Self::check_column_types`].
+    fn from_mbe_row(row: &[TableCell<'_>]) -> Result<Self, RowMappingError>;
+}
+
+///
+/// A type a row can be made of, usually implemented with `#[derive(ToMbeRow)]`. Every
+/// column must be mapped to a field.
+///
+pub trait ToMbeRow {
+    fn to_mbe_row(
+        &self,
+        column_types: &[ColumnType],
+    ) -> Result<Vec<TableCreateCell<'_>>, RowMappingError>;
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use super::{ColumnType, FromMbeCell, RowMappingError, TableCell, TableCreateCell, ToMbeCell};
+
+    pub fn check_length(
+        column_types: &[ColumnType],
+        expected: usize,
+    ) -> Result<(), RowMappingError> {
+        if column_types.len() != expected {
+            return Err(RowMappingError::WrongNumberOfColumns {
+                expected,
+                found: column_types.len(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_field<T: FromMbeCell>(
+        column_types: &[ColumnType],
+        column: usize,
+        field: &'static str,
+    ) -> Result<(), RowMappingError> {
+        let Some(&column_type) = column_types.get(column) else {
+            return Err(RowMappingError::WrongNumberOfColumns {
+                expected: column + 1,
+                found: column_types.len(),
+            });
+        };
+        if !T::accepts(column_type) {
+            return Err(RowMappingError::ColumnTypeMismatch {
+                field,
+                column,
+                found: column_type,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn read_field<T: FromMbeCell>(
+        row: &[TableCell<'_>],
+        column: usize,
+        field: &'static str,
+    ) -> Result<T, RowMappingError> {
+        let &cell = row
+            .get(column)
+            .ok_or(RowMappingError::WrongNumberOfColumns {
+                expected: column + 1,
+                found: row.len(),
+            })?;
+        if !T::accepts(cell.type_()) {
+            return Err(RowMappingError::ColumnTypeMismatch {
+                field,
+                column,
+                found: cell.type_(),
+            });
+        }
+        T::from_mbe_cell(cell).ok_or(RowMappingError::InvalidValue { field, column })
+    }
+
+    pub fn write_field<'a, T: ToMbeCell>(
+        value: &'a T,
+        column_types: &[ColumnType],
+        column: usize,
+        field: &'static str,
+    ) -> Result<TableCreateCell<'a>, RowMappingError> {
+        let column_type = column_types[column];
+        value
+            .to_mbe_cell(column_type)
+            .ok_or(RowMappingError::ColumnTypeMismatch {
+                field,
+                column,
+                found: column_type,
+            })
+    }
+}
+
+impl FromMbeCell for u32 {
+    fn accepts(column_type: ColumnType) -> bool {
+        matches!(column_type, ColumnType::Int | ColumnType::IntID)
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        match cell {
+            TableCell::Int(x) | TableCell::IntID(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl ToMbeCell for u32 {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        match column_type {
+            ColumnType::Int => Some(TableCreateCell::Int(*self)),
+            ColumnType::IntID => Some(TableCreateCell::IntID(*self)),
+            _ => None,
+        }
+    }
+}
+
+impl FromMbeCell for u8 {
+    fn accepts(column_type: ColumnType) -> bool {
+        column_type == ColumnType::Byte
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        match cell {
+            TableCell::Byte(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl ToMbeCell for u8 {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        (column_type == ColumnType::Byte).then_some(TableCreateCell::Byte(*self))
+    }
+}
+
+impl FromMbeCell for f32 {
+    fn accepts(column_type: ColumnType) -> bool {
+        column_type == ColumnType::Float
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        match cell {
+            TableCell::Float(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl ToMbeCell for f32 {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        (column_type == ColumnType::Float).then_some(TableCreateCell::Float(*self))
+    }
+}
+
+impl FromMbeCell for PlaceholderOrCharacter {
+    fn accepts(column_type: ColumnType) -> bool {
+        u32::accepts(column_type)
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        u32::from_mbe_cell(cell).map(Self::from)
+    }
+}
+
+impl ToMbeCell for PlaceholderOrCharacter {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        match column_type {
+            ColumnType::Int => Some(TableCreateCell::Int((*self).into())),
+            ColumnType::IntID => Some(TableCreateCell::IntID((*self).into())),
+            _ => None,
+        }
+    }
+}
+
+fn string_cell(column_type: ColumnType, content: &[u8]) -> Option<TableCreateCell<'_>> {
+    match column_type {
+        ColumnType::String => Some(TableCreateCell::String(Cow::Borrowed(content))),
+        ColumnType::StringID => Some(TableCreateCell::StringID(Cow::Borrowed(content))),
+        _ => None,
+    }
+}
+
+/// The content of a string cell, `None` if it doesn't have one.
+fn string_content(cell: TableCell<'_>) -> Option<Option<&[u8]>> {
+    match cell {
+        TableCell::String(x) | TableCell::StringID(x) => Some(x.map(|x| &x.0)),
+        _ => None,
+    }
+}
+
+fn is_string(column_type: ColumnType) -> bool {
+    matches!(column_type, ColumnType::String | ColumnType::StringID)
+}
+
+/// Strings that aren't valid UTF-8 can't be read into a `String`, use [`ByteString`] for
+/// these. Missing strings are read as empty ones.
+impl FromMbeCell for String {
+    fn accepts(column_type: ColumnType) -> bool {
+        is_string(column_type)
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        let content = string_content(cell)?.unwrap_or_default();
+        String::from_utf8(content.to_vec()).ok()
+    }
+}
+
+impl ToMbeCell for String {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        string_cell(column_type, self.as_bytes())
+    }
+}
+
+impl FromMbeCell for ByteString {
+    fn accepts(column_type: ColumnType) -> bool {
+        is_string(column_type)
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        Some(ByteString(
+            string_content(cell)?.unwrap_or_default().to_vec(),
+        ))
+    }
+}
+
+impl ToMbeCell for ByteString {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        string_cell(column_type, self)
+    }
+}
+
+/// `None` for string cells without a string.
+impl<T: FromMbeCell> FromMbeCell for Option<T> {
+    fn accepts(column_type: ColumnType) -> bool {
+        is_string(column_type) && T::accepts(column_type)
+    }
+
+    fn from_mbe_cell(cell: TableCell<'_>) -> Option<Self> {
+        match string_content(cell)? {
+            Some(_) => T::from_mbe_cell(cell).map(Some),
+            None => Some(None),
+        }
+    }
+}
+
+impl<T: ToMbeCell> ToMbeCell for Option<T> {
+    fn to_mbe_cell(&self, column_type: ColumnType) -> Option<TableCreateCell<'_>> {
+        match self {
+            Some(x) => x.to_mbe_cell(column_type),
+            None => string_cell(column_type, b""),
+        }
+    }
+}
+
+impl<'a> RowSelectioner<'a> {
+    ///
+    /// Reads every row of the sheet as a `T`, checking first that the columns match.
+    ///
+    pub fn read_rows<T: FromMbeRow>(self) -> Result<Vec<T>, RowMappingError> {
+        T::check_column_types(self.column_types())?;
+        (0..self.number_of_row())
+            .flat_map(|x| self.get_row(x))
+            .map(|row| T::from_mbe_row(&row.content()))
+            .collect()
+    }
+}
+
+impl MBEFile {
+    ///
+    /// Reads every row of the sheet named by [`FromMbeRow::SHEET`], which must be set.
+    ///
+    pub fn read_sheet<T: FromMbeRow>(&self) -> Result<Vec<T>, RowMappingError> {
+        let name = T::SHEET.ok_or(RowMappingError::NoSheetName(std::any::type_name::<T>()))?;
+        self.get_sheet_by_name(name.as_bytes())
+            .ok_or_else(|| RowMappingError::SheetNotFound(name.to_string()))?
+            .read_rows()
+    }
+
+    ///
+    /// Inserts `value` as a row at position `row` of a sheet.
+    ///
+    pub fn insert_mapped_row<T: ToMbeRow>(
+        &mut self,
+        sheet: usize,
+        row: usize,
+        value: &T,
+    ) -> Result<(), RowMappingError> {
+        let column_types = self
+            .get_sheet_by_index(sheet)
+            .ok_or(EditMBEFileError::SheetOutOfBounds(sheet))?
+            .column_types()
+            .to_vec();
+        let cells = value.to_mbe_row(&column_types)?;
+        Ok(self.insert_row(sheet, row, cells)?)
+    }
+}
@@ -0,0 +1,298 @@
+use std::{fmt::Display, io};
+
+use regex::bytes::Regex;
+
+use super::ColumnType;
+
+/// The schemas shipped with the crate.
+#[cfg(feature = "serde")]
+const BUNDLED_SCHEMAS: &str = include_str!("../../resources/mbe_schemas.json");
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Io(io::Error),
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(x) => write!(f, "io error: {x}"),
+            #[cfg(feature = "serde")]
+            Self::Json(x) => write!(f, "invalid schema file: {x}"),
+        }
+    }
+}
+impl std::error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(x) => Some(x),
+            #[cfg(feature = "serde")]
+            Self::Json(x) => Some(x),
+        }
+    }
+}
+
+impl From<io::Error> for SchemaError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for SchemaError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+///
+/// What the content of a column means.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ColumnRole {
+    /// The ID the dialogues are matched with when extracting and repacking.
+    MessageId,
+    /// The character saying the message, read as a
+    /// [`PlaceholderOrCharacter`](crate::PlaceholderOrCharacter).
+    Speaker,
+    /// The translatable text.
+    Text,
+    /// The voice line played with the message.
+    VoiceId,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawColumnSchema {
+    name: String,
+    #[serde(default)]
+    role: Option<ColumnRole>,
+    #[serde(default)]
+    types: Option<Vec<String>>,
+}
+
+///
+/// The name and the meaning of a column.
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(try_from = "RawColumnSchema")
+)]
+pub struct ColumnSchema {
+    name: String,
+    role: Option<ColumnRole>,
+    /// The types the column can have, any type if `None`.
+    types: Option<Vec<ColumnType>>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawColumnSchema> for ColumnSchema {
+    type Error = String;
+
+    fn try_from(value: RawColumnSchema) -> Result<Self, Self::Error> {
+        let types = value
+            .types
+            .map(|types| {
+                types
+                    .iter()
+                    .map(|x| {
+                        ColumnType::from_name(x).ok_or_else(|| {
+                            format!("column {:?}: {x:?} isn't a column type", value.name)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        Ok(Self {
+            name: value.name,
+            role: value.role,
+            types,
+        })
+    }
+}
+
+impl ColumnSchema {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn role(&self) -> Option<ColumnRole> {
+        self.role
+    }
+
+    /// Whether a column of type `column_type` can be described by this schema.
+    pub fn accepts(&self, column_type: ColumnType) -> bool {
+        self.types
+            .as_ref()
+            .is_none_or(|types| types.contains(&column_type))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSheetSchema {
+    pattern: String,
+    #[serde(default)]
+    description: Option<String>,
+    columns: Vec<ColumnSchema>,
+}
+
+///
+/// The columns of the sheets whose name matches a pattern.
+///
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(try_from = "RawSheetSchema")
+)]
+pub struct SheetSchema {
+    /// The pattern, anchored so that it has to match the whole name.
+    pattern: Regex,
+    description: Option<String>,
+    columns: Vec<ColumnSchema>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawSheetSchema> for SheetSchema {
+    type Error = regex::Error;
+
+    fn try_from(value: RawSheetSchema) -> Result<Self, Self::Error> {
+        Ok(Self {
+            pattern: Regex::new(&format!("^(?:{})$", value.pattern))?,
+            description: value.description,
+            columns: value.columns,
+        })
+    }
+}
+
+impl SheetSchema {
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn columns(&self) -> &[ColumnSchema] {
+        &self.columns
+    }
+
+    /// The index of the first column having `role`.
+    pub fn column(&self, role: ColumnRole) -> Option<usize> {
+        self.columns.iter().position(|x| x.role == Some(role))
+    }
+
+    pub fn column_name(&self, index: usize) -> Option<&str> {
+        self.columns.get(index).map(ColumnSchema::name)
+    }
+
+    ///
+    /// Whether the schema describes a sheet named `name` with columns of `column_types`:
+    /// the pattern must match the name, and each column must accept its type.
+    ///
+    pub fn matches(&self, name: &[u8], column_types: &[ColumnType]) -> bool {
+        self.pattern.is_match(name)
+            && self.columns.len() == column_types.len()
+            && self
+                .columns
+                .iter()
+                .zip(column_types)
+                .all(|(column, &type_)| column.accepts(type_))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSchemaRegistry {
+    sheets: Vec<SheetSchema>,
+}
+
+///
+/// The known layouts of sheets, used to know which column holds what instead of guessing
+/// it from the column types.
+///
+/// A registry is read from a JSON file listing sheet schemas, if the `serde` feature is
+/// enabled:
+///
+/// ```json
+/// {
+///     "sheets": [
+///         {
+///             "pattern": "message_.*",
+///             "columns": [
+///                 { "name": "id", "role": "message_id", "types": ["IntID"] },
+///                 { "name": "speaker", "role": "speaker", "types": ["Int"] },
+///                 { "name": "text", "role": "text", "types": ["String"] }
+///             ]
+///         }
+///     ]
+/// }
+/// ```
+///
+/// The patterns are regular expressions matching the whole sheet name. When several
+/// schemas match a sheet, the last one wins, so that [`SchemaRegistry::extend`] can
+/// override the bundled schemas.
+///
+#[derive(Debug, Clone)]
+pub struct SchemaRegistry {
+    sheets: Vec<SheetSchema>,
+}
+
+/// The bundled schemas, which are read from JSON. Without the `serde` feature, a registry
+/// knowing no sheet.
+impl Default for SchemaRegistry {
+    fn default() -> Self {
+        #[cfg(feature = "serde")]
+        return Self::bundled();
+        #[cfg(not(feature = "serde"))]
+        Self::empty()
+    }
+}
+
+impl SchemaRegistry {
+    /// A registry knowing no sheet.
+    pub const fn empty() -> Self {
+        Self { sheets: Vec::new() }
+    }
+
+    /// The schemas shipped with the crate, from `resources/mbe_schemas.json`.
+    #[cfg(feature = "serde")]
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_SCHEMAS).expect("the bundled schemas should be valid")
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(source: &str) -> Result<Self, SchemaError> {
+        let raw: RawSchemaRegistry = serde_json::from_str(source)?;
+        Ok(Self { sheets: raw.sheets })
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_path(source: impl AsRef<std::path::Path>) -> Result<Self, SchemaError> {
+        Self::from_json(&std::fs::read_to_string(source)?)
+    }
+
+    /// Adds the schemas of `other`, which take precedence over the current ones.
+    pub fn extend(&mut self, other: SchemaRegistry) {
+        self.sheets.extend(other.sheets);
+    }
+
+    pub fn sheets(&self) -> &[SheetSchema] {
+        &self.sheets
+    }
+
+    /// The schema of a sheet named `name` with columns of `column_types`, if it's known.
+    pub fn find(&self, name: &[u8], column_types: &[ColumnType]) -> Option<&SheetSchema> {
+        self.sheets
+            .iter()
+            .rev()
+            .find(|x| x.matches(name, column_types))
+    }
+}
@@ -1,5 +1,9 @@
+pub mod delta;
 mod extract;
 mod iterate;
+mod layout;
+mod manifest;
+mod mbe;
 mod pack;
 
 use std::{
@@ -11,13 +15,15 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
 pub use extract::Extractor;
 pub use iterate::ContentIterator;
+pub use layout::FileHeader;
+use layout::{PathEntry, SizeEntry, TrieNode};
 use lz4::block::CompressionMode;
+pub use manifest::{Manifest, ManifestEntry, ManifestMismatch, hash_content};
 pub use pack::Packer;
 
-use crate::helpers::traits::ReadSeek;
+use crate::helpers::{binary::BinaryLayout, traits::ReadSeek};
 
 pub struct MVGLArchive<R: ReadSeek> {
     header: FileHeader,
@@ -35,75 +41,35 @@ impl<R: ReadSeek> MVGLArchive<R> {
     }
 
     fn parse_header(reader: &mut R) -> io::Result<(FileHeader, Vec<FileInfo>)> {
-        let mut magic_number = [0; 4];
-
-        reader.read_exact(&mut magic_number)?;
-        assert_eq!(&magic_number, b"MDB1");
-
-        let header = FileHeader {
-            file_entry_count: reader.read_u32::<LittleEndian>()?,
-            file_name_count: reader.read_u32::<LittleEndian>()?,
-            data_entry_count: reader.read_u32::<LittleEndian>()?,
-            data_start: reader.read_u64::<LittleEndian>()?,
-            total_size: reader.read_u64::<LittleEndian>()?,
-        };
-
-        let mut sep1 = [0; 16];
-        reader.read_exact(&mut sep1)?;
-        assert_eq!(
-            &sep1,
-            [
-                0xff_u8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 1, 0, 0, 0
-            ]
-            .as_slice()
-        );
-
-        let mut structures = Vec::with_capacity(header.data_entry_count as usize);
-
-        for _ in 0..header.data_entry_count {
-            let _compare_byte = reader.read_u32::<LittleEndian>()?;
-            let id = reader.read_u32::<LittleEndian>()?;
-            let _left = reader.read_u32::<LittleEndian>()?;
-            let _right = reader.read_u32::<LittleEndian>()?;
-            structures.push(FileEntry {
-                id,
-                name: String::new(),
-            });
-        }
-
-        let mut buffer = [0; 0x80];
-        reader.read_exact(&mut buffer)?;
-
-        for entry in &mut structures {
-            reader.read_exact(&mut buffer)?;
-            let extension = buffer[..4]
-                .iter()
-                .map(|&x| x as char)
-                .take_while(|&x| x != ' ');
-            let file = buffer[4..]
-                .iter()
-                .take_while(|&&x| x != 0)
-                .map(|&x| x as char)
-                .map(|x| if x == '\\' { '/' } else { x })
-                .chain(std::iter::once('.'))
-                .chain(extension)
-                .collect::<String>();
-            entry.name = file;
-        }
+        let header = FileHeader::read_from(reader)?;
+
+        let nodes = (0..header.data_entry_count)
+            .map(|_| TrieNode::read_from(reader))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // The path of the root of the tree
+        PathEntry::read_from(reader)?;
+        let mut structures = nodes
+            .into_iter()
+            .map(|node| {
+                Ok(FileEntry {
+                    id: node.id,
+                    name: PathEntry::read_from(reader)?.name(),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
 
         let mut file_infos = Vec::with_capacity(header.data_entry_count as usize);
 
         for i in 0..header.data_entry_count {
-            let offset = reader.read_u64::<LittleEndian>()?;
-            let uncompressed_size = reader.read_u64::<LittleEndian>()?;
-            let compressed_size = reader.read_u64::<LittleEndian>()?;
+            let size = SizeEntry::read_from(reader)?;
 
             let position = structures.iter().position(|x| x.id == i).unwrap();
             let structure = structures.swap_remove(position);
             file_infos.push(FileInfo {
-                offset,
-                decompressed_size: uncompressed_size,
-                compressed_size,
+                offset: size.offset,
+                decompressed_size: size.decompressed_size,
+                compressed_size: size.compressed_size,
                 id: structure.id,
                 name: structure.name,
             });
@@ -231,15 +197,6 @@ impl DecompressedFile {
     }
 }
 
-#[allow(dead_code)]
-pub struct FileHeader {
-    file_entry_count: u32,
-    file_name_count: u32,
-    data_entry_count: u32,
-    data_start: u64,
-    total_size: u64,
-}
-
 pub struct FileEntry {
     pub id: u32,
     pub name: String,
@@ -0,0 +1,592 @@
+//!
+//! Binary patches between two versions of an archive.
+//!
+//! A [`Delta`] only contains the entries that differ between a vanilla archive and a
+//! modified one, along with the hash of the vanilla content they replace. Applying it to
+//! the same vanilla archive rebuilds the modified one without redistributing it.
+//!
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, Read, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::helpers::{
+    cancellation::{CancellationToken, check_cancellation},
+    progress::{ProgressReporter, ProgressUnit, default_progress_reporter},
+    traits::{ReadSeek, ReadWriteSeekSend},
+};
+
+use super::{
+    CompressedFile, MVGLArchive, Manifest, ManifestEntry, ManifestMismatch, Packer, SlicedPath,
+    hash_content,
+};
+
+const MAGIC_NUMBER: &[u8; 4] = b"MVDL";
+const VERSION: u32 = 1;
+
+/// The longest path or hash a patch may contain.
+const MAX_STRING_LENGTH: usize = 0x1000;
+
+#[derive(Debug)]
+pub enum DeltaError {
+    BadMagicNumber,
+    UnsupportedVersion(u32),
+    InvalidEntryKind(u8),
+    /// The vanilla archive doesn't contain an entry the patch modifies.
+    MissingEntry(String),
+    /// The vanilla archive already contains an entry the patch adds.
+    ExistingEntry(String),
+    /// An entry doesn't have the content the patch expects.
+    HashMismatch {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    /// An entry of the patch couldn't be decompressed.
+    CorruptedEntry(String),
+    /// The patched archive isn't the one the patch should produce.
+    OutputMismatch(Vec<ManifestMismatch>),
+    Io(io::Error),
+}
+
+impl Display for DeltaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagicNumber => write!(f, "expected MVDL as a magic number"),
+            Self::UnsupportedVersion(x) => write!(f, "unsupported delta version {x}"),
+            Self::InvalidEntryKind(x) => write!(f, "invalid delta entry kind {x}"),
+            Self::MissingEntry(path) => write!(f, "{path} isn't in the vanilla archive"),
+            Self::ExistingEntry(path) => write!(f, "{path} is already in the vanilla archive"),
+            Self::HashMismatch {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path}: expected hash {expected}, found {found}"),
+            Self::CorruptedEntry(path) => write!(f, "{path}: couldn't decompress patched content"),
+            Self::OutputMismatch(mismatches) => {
+                write!(f, "the patched archive differs from the expected one:")?;
+                for mismatch in mismatches {
+                    write!(f, "\n{mismatch}")?;
+                }
+                Ok(())
+            }
+            Self::Io(x) => write!(f, "io error: {x}"),
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DeltaError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+pub enum DeltaEntry {
+    /// The entry exists in both archives, with a different content.
+    Modified {
+        path: String,
+        original_sha256: String,
+        sha256: String,
+        content: CompressedFile,
+    },
+    /// The entry only exists in the modified archive.
+    Added {
+        path: String,
+        sha256: String,
+        content: CompressedFile,
+    },
+    /// The entry only exists in the vanilla archive.
+    Removed {
+        path: String,
+        original_sha256: String,
+    },
+}
+
+impl DeltaEntry {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Modified { path, .. } | Self::Added { path, .. } | Self::Removed { path, .. } => {
+                path
+            }
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Self::Modified { .. } => 0,
+            Self::Added { .. } => 1,
+            Self::Removed { .. } => 2,
+        }
+    }
+}
+
+fn write_size_prefixed(writer: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+fn read_size_prefixed_string(reader: &mut dyn Read) -> io::Result<String> {
+    let length = reader.read_u32::<LittleEndian>()? as usize;
+    if length > MAX_STRING_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("string of {length} bytes, longer than {MAX_STRING_LENGTH}"),
+        ));
+    }
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer)?;
+    String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_content(writer: &mut dyn Write, content: &CompressedFile) -> io::Result<()> {
+    writer.write_u64::<LittleEndian>(content.decompressed_size as u64)?;
+    writer.write_u64::<LittleEndian>(content.content.len() as u64)?;
+    writer.write_all(&content.content)
+}
+
+fn read_content(reader: &mut dyn Read) -> io::Result<CompressedFile> {
+    let decompressed_size = reader.read_u64::<LittleEndian>()?;
+    let compressed_size = reader.read_u64::<LittleEndian>()?;
+    // LZ4 blocks can't be larger than that
+    if decompressed_size > i32::MAX as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "content of {decompressed_size} bytes, larger than {}",
+                i32::MAX
+            ),
+        ));
+    }
+    // The content is read as it comes instead of being allocated from the untrusted size
+    let mut content = Vec::new();
+    reader.take(compressed_size).read_to_end(&mut content)?;
+    if content.len() as u64 != compressed_size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(CompressedFile {
+        content,
+        decompressed_size: decompressed_size as usize,
+    })
+}
+
+fn decompressed_hash(path: &str, content: &CompressedFile) -> Result<String, DeltaError> {
+    let decompressed = content
+        .decompress()
+        .ok_or_else(|| DeltaError::CorruptedEntry(path.to_string()))?;
+    Ok(hash_content(decompressed.as_slice()))
+}
+
+///
+/// The list of entries changed between a vanilla archive and a modified one.
+///
+pub struct Delta {
+    entries: Vec<DeltaEntry>,
+}
+
+impl Delta {
+    ///
+    /// Computes the changes needed to go from `vanilla` to `modified`.
+    ///
+    pub fn create<R1: ReadSeek + Send, R2: ReadSeek + Send>(
+        vanilla: &MVGLArchive<R1>,
+        modified: &MVGLArchive<R2>,
+    ) -> io::Result<Self> {
+        let vanilla_manifest = Manifest::from_archive(vanilla)?;
+        let modified_manifest = Manifest::from_archive(modified)?;
+        let mut entries = Vec::new();
+
+        for entry in modified_manifest.entries() {
+            let original = vanilla_manifest.get(&entry.path);
+            if original.is_some_and(|x| x.sha256 == entry.sha256) {
+                continue;
+            }
+            let content = modified.get(&entry.path).unwrap()?;
+            entries.push(match original {
+                Some(original) => DeltaEntry::Modified {
+                    path: entry.path.clone(),
+                    original_sha256: original.sha256.clone(),
+                    sha256: entry.sha256.clone(),
+                    content,
+                },
+                None => DeltaEntry::Added {
+                    path: entry.path.clone(),
+                    sha256: entry.sha256.clone(),
+                    content,
+                },
+            });
+        }
+
+        entries.extend(
+            vanilla_manifest
+                .entries()
+                .iter()
+                .filter(|entry| modified_manifest.get(&entry.path).is_none())
+                .map(|entry| DeltaEntry::Removed {
+                    path: entry.path.clone(),
+                    original_sha256: entry.sha256.clone(),
+                }),
+        );
+
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[DeltaEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC_NUMBER)?;
+        writer.write_u32::<LittleEndian>(VERSION)?;
+        writer.write_u32::<LittleEndian>(self.entries.len() as u32)?;
+
+        for entry in &self.entries {
+            writer.write_u8(entry.kind())?;
+            write_size_prefixed(writer, entry.path().as_bytes())?;
+            match entry {
+                DeltaEntry::Modified {
+                    original_sha256,
+                    sha256,
+                    content,
+                    ..
+                } => {
+                    write_size_prefixed(writer, original_sha256.as_bytes())?;
+                    write_size_prefixed(writer, sha256.as_bytes())?;
+                    write_content(writer, content)?;
+                }
+                DeltaEntry::Added {
+                    sha256, content, ..
+                } => {
+                    write_size_prefixed(writer, sha256.as_bytes())?;
+                    write_content(writer, content)?;
+                }
+                DeltaEntry::Removed {
+                    original_sha256, ..
+                } => {
+                    write_size_prefixed(writer, original_sha256.as_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read(reader: &mut dyn Read) -> Result<Self, DeltaError> {
+        let mut magic_number = [0; 4];
+        reader.read_exact(&mut magic_number)?;
+        if &magic_number != MAGIC_NUMBER {
+            return Err(DeltaError::BadMagicNumber);
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(DeltaError::UnsupportedVersion(version));
+        }
+
+        let number_of_entries = reader.read_u32::<LittleEndian>()?;
+        let entries = (0..number_of_entries)
+            .map(|_| {
+                let kind = reader.read_u8()?;
+                let path = read_size_prefixed_string(reader)?;
+                Ok(match kind {
+                    0 => DeltaEntry::Modified {
+                        path,
+                        original_sha256: read_size_prefixed_string(reader)?,
+                        sha256: read_size_prefixed_string(reader)?,
+                        content: read_content(reader)?,
+                    },
+                    1 => DeltaEntry::Added {
+                        path,
+                        sha256: read_size_prefixed_string(reader)?,
+                        content: read_content(reader)?,
+                    },
+                    2 => DeltaEntry::Removed {
+                        path,
+                        original_sha256: read_size_prefixed_string(reader)?,
+                    },
+                    x => return Err(DeltaError::InvalidEntryKind(x)),
+                })
+            })
+            .collect::<Result<Vec<_>, DeltaError>>()?;
+
+        Ok(Self { entries })
+    }
+
+    ///
+    /// Checks that `vanilla` is the archive this patch was built against.
+    ///
+    /// Every entry the patch modifies or removes must have the expected content, and the
+    /// entries it adds must not be there.
+    ///
+    pub fn check<R: ReadSeek + Send>(&self, vanilla: &MVGLArchive<R>) -> Result<(), DeltaError> {
+        self.check_manifest(&Manifest::from_archive(vanilla)?)
+    }
+
+    fn check_manifest(&self, manifest: &Manifest) -> Result<(), DeltaError> {
+        for entry in &self.entries {
+            if let DeltaEntry::Added { path, .. } = entry
+                && manifest.get(path).is_some()
+            {
+                return Err(DeltaError::ExistingEntry(path.clone()));
+            }
+            let (DeltaEntry::Modified {
+                path,
+                original_sha256,
+                ..
+            }
+            | DeltaEntry::Removed {
+                path,
+                original_sha256,
+            }) = entry
+            else {
+                continue;
+            };
+            let Some(found) = manifest.get(path) else {
+                return Err(DeltaError::MissingEntry(path.clone()));
+            };
+            if &found.sha256 != original_sha256 {
+                return Err(DeltaError::HashMismatch {
+                    path: path.clone(),
+                    expected: original_sha256.clone(),
+                    found: found.sha256.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The manifest of the archive this patch produces from `vanilla`.
+    fn target_manifest(&self, vanilla: &Manifest) -> Manifest {
+        let mut entries = vanilla
+            .entries()
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.clone()))
+            .collect::<HashMap<_, _>>();
+        for entry in &self.entries {
+            match entry {
+                DeltaEntry::Modified {
+                    path,
+                    sha256,
+                    content,
+                    ..
+                }
+                | DeltaEntry::Added {
+                    path,
+                    sha256,
+                    content,
+                } => {
+                    entries.insert(
+                        path,
+                        ManifestEntry {
+                            path: path.clone(),
+                            decompressed_size: content.decompressed_size as u64,
+                            compressed_size: content.content.len() as u64,
+                            sha256: sha256.clone(),
+                        },
+                    );
+                }
+                DeltaEntry::Removed { path, .. } => {
+                    entries.remove(path.as_str());
+                }
+            }
+        }
+        Manifest::from_entries(entries.into_values().collect())
+    }
+
+    ///
+    /// Rebuilds the modified archive from `vanilla`, writing it in `destination`. See
+    /// [`DeltaApplier::apply`], which also reports the progress and can be cancelled.
+    ///
+    pub fn apply<R: ReadSeek + Send>(
+        &self,
+        vanilla: &MVGLArchive<R>,
+        destination: &mut dyn ReadWriteSeekSend,
+    ) -> Result<(), DeltaError> {
+        DeltaApplier::new().apply(self, vanilla, destination)
+    }
+}
+
+///
+/// Applies a [`Delta`], like [`Packer`] packs a directory.
+///
+pub struct DeltaApplier {
+    progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    cancellation_token: Option<CancellationToken>,
+}
+
+impl Default for DeltaApplier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeltaApplier {
+    pub const fn new() -> Self {
+        Self {
+            progress_reporter: None,
+            cancellation_token: None,
+        }
+    }
+
+    ///
+    /// Sets where the progress of the patching is reported. Defaults to
+    /// [`IndicatifProgress`](crate::helpers::progress::IndicatifProgress) if the `indicatif`
+    /// feature is enabled, and to nothing otherwise.
+    ///
+    pub fn with_progress_reporter(
+        self,
+        progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    ) -> Self {
+        Self {
+            progress_reporter,
+            ..self
+        }
+    }
+
+    ///
+    /// Sets a token that can be used to stop the patching between two files.
+    ///
+    /// The archive written so far is incomplete, and mustn't replace anything.
+    ///
+    pub fn with_cancellation_token(self, cancellation_token: Option<CancellationToken>) -> Self {
+        Self {
+            cancellation_token,
+            ..self
+        }
+    }
+
+    ///
+    /// Rebuilds the modified archive from `vanilla`, writing it in `destination`.
+    ///
+    /// `vanilla` is checked with [`Delta::check`] beforehand, and the patched content is
+    /// checked against the hashes stored in the patch before anything is written. Once
+    /// written, `destination` is read back and checked against the archive the patch was
+    /// built from. Unchanged entries are copied without being recompressed.
+    ///
+    pub fn apply<R: ReadSeek + Send>(
+        &self,
+        delta: &Delta,
+        vanilla: &MVGLArchive<R>,
+        destination: &mut dyn ReadWriteSeekSend,
+    ) -> Result<(), DeltaError> {
+        let progress_reporter = self
+            .progress_reporter
+            .clone()
+            .unwrap_or_else(default_progress_reporter);
+
+        let checking_progress = progress_reporter.start_phase(
+            "checking the vanilla archive",
+            None,
+            ProgressUnit::Items,
+        );
+        let vanilla_manifest = Manifest::from_archive(vanilla)?;
+        delta.check_manifest(&vanilla_manifest)?;
+        checking_progress.finish("finished checking the vanilla archive");
+        check_cancellation(self.cancellation_token.as_ref())?;
+
+        let mut patched = HashMap::new();
+        for entry in &delta.entries {
+            match entry {
+                DeltaEntry::Modified {
+                    path,
+                    sha256,
+                    content,
+                    ..
+                }
+                | DeltaEntry::Added {
+                    path,
+                    sha256,
+                    content,
+                } => {
+                    let found = decompressed_hash(path, content)?;
+                    if &found != sha256 {
+                        return Err(DeltaError::HashMismatch {
+                            path: path.clone(),
+                            expected: sha256.clone(),
+                            found,
+                        });
+                    }
+                    patched.insert(path.as_str(), Some(content));
+                }
+                DeltaEntry::Removed { path, .. } => {
+                    patched.insert(path.as_str(), None);
+                }
+            }
+        }
+
+        let all_paths = vanilla
+            .infos
+            .iter()
+            .map(|info| info.name.as_str())
+            .filter(|path| !matches!(patched.get(path), Some(None)))
+            .chain(delta.entries.iter().filter_map(|entry| match entry {
+                DeltaEntry::Added { path, .. } => Some(path.as_str()),
+                _ => None,
+            }))
+            .map(|path| {
+                SlicedPath::new(Path::new(path)).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{path}: path without extension"),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut unchanged = vanilla
+            .iter()
+            .map(|handle| (handle.info.name.as_str(), handle))
+            .collect::<HashMap<_, _>>();
+        Packer::new()
+            .with_progress_reporter(Some(progress_reporter.clone()))
+            .with_cancellation_token(self.cancellation_token.clone())
+            .pack_entries(&all_paths, &mut &mut *destination, |entry| {
+                let path = entry.to_string();
+                match patched.get(path.as_str()) {
+                    Some(Some(content)) => Ok(CompressedFile {
+                        content: content.content.clone(),
+                        decompressed_size: content.decompressed_size,
+                    }),
+                    _ => unchanged
+                        .remove(path.as_str())
+                        .ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::NotFound, format!("{path}: not found"))
+                        })?
+                        .read(),
+                }
+            })?;
+
+        let checking_progress = progress_reporter.start_phase(
+            "checking the patched archive",
+            None,
+            ProgressUnit::Items,
+        );
+        destination.seek(SeekFrom::Start(0))?;
+        let mismatches = delta
+            .target_manifest(&vanilla_manifest)
+            .verify_archive(&MVGLArchive::from_reader(&mut *destination)?)?;
+        if !mismatches.is_empty() {
+            return Err(DeltaError::OutputMismatch(mismatches));
+        }
+        checking_progress.finish("finished checking the patched archive");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//!
+//! The structures of the header of an MVGL archive, as described by `resources/mvgl.ksy`.
+//!
+//! The header is followed by a binary tree to find files by path, whose root is always
+//! the same and is part of the header, then by the path of each node of the tree and by
+//! where the content of each file is.
+//!
+
+use std::io;
+
+use crate::helpers::binary::binary_layout;
+
+use super::SlicedPath;
+
+binary_layout! {
+    pub struct FileHeader {
+        const b"MDB1",
+        /// The number of nodes of the tree, its root included.
+        pub(super) file_entry_count: u32,
+        /// The number of paths, the empty one of the root included.
+        pub(super) file_name_count: u32,
+        pub(super) data_entry_count: u32,
+        pub(super) data_start: u64,
+        pub(super) total_size: u64,
+        // The root of the tree, which doesn't stand for any file
+        const b"\xff\xff\xff\xff\xff\xff\xff\xff\x00\x00\x00\x00\x01\x00\x00\x00",
+    }
+}
+
+impl FileHeader {
+    const SIZE: u64 = 48;
+
+    ///
+    /// The header of an archive holding `number_of_paths` files, whose content is
+    /// `content_size` bytes long.
+    ///
+    pub(super) fn new(number_of_paths: u32, content_size: u64) -> Self {
+        let paths = number_of_paths as u64;
+        // The root of the tree has a node and an empty path, but no content
+        let data_start = Self::SIZE
+            + paths * TrieNode::SIZE
+            + (paths + 1) * PathEntry::SIZE
+            + paths * SizeEntry::SIZE;
+        Self {
+            file_entry_count: number_of_paths + 1,
+            file_name_count: number_of_paths + 1,
+            data_entry_count: number_of_paths,
+            data_start,
+            total_size: data_start + content_size,
+        }
+    }
+
+    /// The offset of the first [`SizeEntry`] in the archive.
+    pub(super) fn size_entries_start(&self) -> u64 {
+        self.data_start - self.data_entry_count as u64 * SizeEntry::SIZE
+    }
+}
+
+binary_layout! {
+    /// A node of the tree, other than its root.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub(super) struct TrieNode {
+        pub(super) compare_bit: u32,
+        /// The index of the file in the [`SizeEntry`] list.
+        pub(super) id: u32,
+        pub(super) left: u32,
+        pub(super) right: u32,
+    }
+}
+
+impl TrieNode {
+    const SIZE: u64 = 16;
+}
+
+binary_layout! {
+    ///
+    /// The path of a node of the tree, without its extension and with `\` as separator,
+    /// padded with NULs.
+    ///
+    pub(super) struct PathEntry {
+        /// The extension, padded with spaces.
+        extension: [u8; 4],
+        path: [u8; 0x7c],
+    }
+}
+
+impl PathEntry {
+    const SIZE: u64 = 0x80;
+
+    /// The path of the root of the tree.
+    pub(super) const EMPTY: Self = Self {
+        extension: [0; 4],
+        path: [0; 0x7c],
+    };
+
+    pub(super) fn new(path: &SlicedPath) -> io::Result<Self> {
+        let file = path.file.replace('/', "\\");
+        let mut entry = Self {
+            extension: path.extension,
+            ..Self::EMPTY
+        };
+        entry
+            .path
+            .get_mut(..file.len())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{path}: path longer than {} bytes", Self::EMPTY.path.len()),
+                )
+            })?
+            .copy_from_slice(file.as_bytes());
+        Ok(entry)
+    }
+
+    /// The path, with `/` as separator and its extension.
+    pub(super) fn name(&self) -> String {
+        let extension = self
+            .extension
+            .iter()
+            .map(|&x| x as char)
+            .take_while(|&x| x != ' ');
+        self.path
+            .iter()
+            .take_while(|&&x| x != 0)
+            .map(|&x| x as char)
+            .map(|x| if x == '\\' { '/' } else { x })
+            .chain(std::iter::once('.'))
+            .chain(extension)
+            .collect()
+    }
+}
+
+binary_layout! {
+    /// Where the content of a file is, from the start of the data.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub(super) struct SizeEntry {
+        pub(super) offset: u64,
+        pub(super) decompressed_size: u64,
+        pub(super) compressed_size: u64,
+    }
+}
+
+impl SizeEntry {
+    const SIZE: u64 = 24;
+}
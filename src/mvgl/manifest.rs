@@ -0,0 +1,273 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt::Display,
+    fs, io,
+    path::Path,
+};
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::helpers::traits::ReadSeek;
+
+use super::{CompressedFileHandle, DecompressedFile, MVGLArchive};
+
+///
+/// Computes the lowercase hexadecimal SHA-256 of `content`.
+///
+pub fn hash_content(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+///
+/// Description of a single entry of an archive.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    pub path: String,
+    pub decompressed_size: u64,
+    pub compressed_size: u64,
+    /// SHA-256 of the decompressed content, as lowercase hexadecimal.
+    pub sha256: String,
+}
+
+impl ManifestEntry {
+    fn from_handle<R: ReadSeek>(handle: CompressedFileHandle<'_, R>) -> io::Result<Self> {
+        let path = handle.info.name.clone();
+        let decompressed_size = handle.info.decompressed_size;
+        let compressed_size = handle.info.compressed_size;
+        let content = handle.read()?;
+        let content = content
+            .decompress()
+            .map_or_else(|| content.into_inner(), DecompressedFile::into_inner);
+        Ok(Self {
+            path,
+            decompressed_size,
+            compressed_size,
+            sha256: hash_content(&content),
+        })
+    }
+}
+
+///
+/// A difference found while verifying something against a [`Manifest`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMismatch {
+    /// The manifest lists this path, but it wasn't found.
+    Missing(String),
+    /// This path was found, but the manifest doesn't list it.
+    Unexpected(String),
+    /// The decompressed content doesn't have the expected size.
+    SizeDiffers {
+        path: String,
+        expected: u64,
+        found: u64,
+    },
+    /// The decompressed content doesn't have the expected hash.
+    HashDiffers {
+        path: String,
+        expected: String,
+        found: String,
+    },
+}
+
+impl Display for ManifestMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "{path}: missing"),
+            Self::Unexpected(path) => write!(f, "{path}: not in manifest"),
+            Self::SizeDiffers {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path}: expected {expected} bytes, found {found}"),
+            Self::HashDiffers {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path}: expected hash {expected}, found {found}"),
+        }
+    }
+}
+
+///
+/// The list of every entry of an archive, with their sizes and the hash of their content.
+///
+/// Used to check that an archive (or its extracted content) is exactly the one a patch
+/// was built against.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    ///
+    /// Computes the manifest of `archive`, decompressing and hashing entries in parallel.
+    ///
+    pub fn from_archive<R: ReadSeek + Send>(archive: &MVGLArchive<R>) -> io::Result<Self> {
+        let mut entries = archive
+            .iter()
+            .par_bridge()
+            .map(ManifestEntry::from_handle)
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+
+    pub(super) fn from_entries(mut entries: Vec<ManifestEntry>) -> Self {
+        entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.entries
+            .binary_search_by(|entry| entry.path.as_str().cmp(path))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(reader: &mut dyn io::Read) -> io::Result<Self> {
+        let mut manifest: Self = serde_json::from_reader(reader)?;
+        manifest
+            .entries
+            .sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(manifest)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_csv(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for entry in &self.entries {
+            writer.serialize(entry)?;
+        }
+        writer.flush()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_csv(reader: &mut dyn io::Read) -> io::Result<Self> {
+        let mut entries = csv::Reader::from_reader(reader)
+            .into_deserialize()
+            .collect::<Result<Vec<ManifestEntry>, _>>()?;
+        entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+
+    fn compare(&self, other: &Self) -> Vec<ManifestMismatch> {
+        let found = other
+            .entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect::<HashMap<_, _>>();
+
+        let mut mismatches = Vec::new();
+        for expected in &self.entries {
+            let Some(&found) = found.get(expected.path.as_str()) else {
+                mismatches.push(ManifestMismatch::Missing(expected.path.clone()));
+                continue;
+            };
+            if expected.decompressed_size != found.decompressed_size {
+                mismatches.push(ManifestMismatch::SizeDiffers {
+                    path: expected.path.clone(),
+                    expected: expected.decompressed_size,
+                    found: found.decompressed_size,
+                });
+            } else if expected.sha256 != found.sha256 {
+                mismatches.push(ManifestMismatch::HashDiffers {
+                    path: expected.path.clone(),
+                    expected: expected.sha256.clone(),
+                    found: found.sha256.clone(),
+                });
+            }
+        }
+
+        let expected = self
+            .entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect::<HashSet<_>>();
+        mismatches.extend(
+            other
+                .entries
+                .iter()
+                .filter(|entry| !expected.contains(entry.path.as_str()))
+                .map(|entry| ManifestMismatch::Unexpected(entry.path.clone())),
+        );
+
+        mismatches
+    }
+
+    ///
+    /// Checks `archive` against this manifest. An empty result means both are identical.
+    ///
+    /// Only decompressed content is compared, so an archive recompressed with different
+    /// settings still matches.
+    ///
+    pub fn verify_archive<R: ReadSeek + Send>(
+        &self,
+        archive: &MVGLArchive<R>,
+    ) -> io::Result<Vec<ManifestMismatch>> {
+        Ok(self.compare(&Self::from_archive(archive)?))
+    }
+
+    ///
+    /// Checks an extracted archive against this manifest. An empty result means the
+    /// directory contains exactly the files of the manifest, with the same content.
+    ///
+    /// `rename_images` must be what the archive was extracted with, see
+    /// [`Extractor::with_rename_images`](super::Extractor::with_rename_images): `.dds` files
+    /// are then compared to the `.img` entries of the manifest.
+    ///
+    pub fn verify_directory(
+        &self,
+        directory: &Path,
+        rename_images: bool,
+    ) -> io::Result<Vec<ManifestMismatch>> {
+        // Entries that can't be read fail the verification, instead of being skipped
+        let paths = WalkDir::new(directory)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Ok(entry) if !entry.file_type().is_file() => None,
+                entry => Some(entry.map(walkdir::DirEntry::into_path)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let entries = paths
+            .into_par_iter()
+            .map(|path| {
+                let content = fs::read(&path)?;
+                let mut relative_path = path.strip_prefix(directory).unwrap().to_path_buf();
+                if rename_images && relative_path.extension() == Some(OsStr::new("dds")) {
+                    relative_path.set_extension("img");
+                }
+                Ok(ManifestEntry {
+                    path: relative_path.to_string_lossy().replace('\\', "/"),
+                    decompressed_size: content.len() as u64,
+                    compressed_size: 0,
+                    sha256: hash_content(&content),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(self.compare(&Self { entries }))
+    }
+}
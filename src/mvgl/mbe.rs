@@ -0,0 +1,58 @@
+use std::{
+    ffi::OsStr,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use rayon::iter::{ParallelBridge, ParallelIterator};
+
+use crate::{
+    helpers::{offset_wrapper::OffsetReadWrapper, traits::ReadSeek},
+    mbe::{MBEFile, ParseMBEFileError},
+};
+
+use super::{CompressedFile, DecompressedFile, MVGLArchive};
+
+///
+/// Parses the content of the entry at `path`. Entries that can't be decompressed are
+/// parsed as is, like [`Extractor`](super::Extractor) writes them.
+///
+fn parse_entry(
+    path: &str,
+    content: io::Result<CompressedFile>,
+) -> Result<MBEFile, ParseMBEFileError> {
+    let content = content.map_err(|x| ParseMBEFileError::from(x).with_path(path))?;
+    let content = content
+        .decompress()
+        .map_or_else(|| content.into_inner(), DecompressedFile::into_inner);
+    MBEFile::parse(&mut OffsetReadWrapper::new(&mut Cursor::new(content)))
+        .map_err(|x| x.with_path(path))
+}
+
+impl<R: ReadSeek> MVGLArchive<R> {
+    ///
+    /// Reads and parses the MBE file at `path`, if the archive has such an entry. The
+    /// errors carry `path`.
+    ///
+    pub fn get_mbe(&self, path: &str) -> Option<Result<MBEFile, ParseMBEFileError>> {
+        self.get(path).map(|content| parse_entry(path, content))
+    }
+}
+
+impl<R: ReadSeek + Send> MVGLArchive<R> {
+    ///
+    /// Reads and parses every `.mbe` entry of the archive in parallel, in no particular
+    /// order, along with their path. The errors carry the path of the entry they come from.
+    ///
+    pub fn iter_mbe(
+        &self,
+    ) -> impl ParallelIterator<Item = (&str, Result<MBEFile, ParseMBEFileError>)> + '_ {
+        self.iter()
+            .filter(|x| Path::new(&x.info.name).extension() == Some(OsStr::new("mbe")))
+            .par_bridge()
+            .map(|handle| {
+                let path = handle.info.name.as_str();
+                (path, parse_entry(path, handle.read()))
+            })
+    }
+}
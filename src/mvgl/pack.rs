@@ -1,18 +1,29 @@
-use std::{borrow::Cow, ffi::OsStr, fs, io::SeekFrom, path::Path, time::Duration};
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fs,
+    io::{self, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
 
-use byteorder::{LittleEndian, WriteBytesExt};
-use indicatif::{MultiProgress, ProgressBar, ProgressFinish, ProgressIterator};
+#[cfg(feature = "indicatif")]
+use indicatif::MultiProgress;
 use lz4::block::CompressionMode;
 use walkdir::WalkDir;
 
 use crate::helpers::{
-    indicatif::{
-        IndicatifProgressExt, default_bar_style_with_message_header, default_spinner_style,
-    },
+    atomic_file::write_atomically,
+    binary::BinaryLayout,
+    cancellation::{CancellationToken, check_cancellation},
+    progress::{ProgressReporter, ProgressUnit, default_progress_reporter},
     traits::WriteSeek,
 };
 
-use super::{EMPTY_SLICED_PATH, SlicedPath};
+use super::{
+    CompressedFile, EMPTY_SLICED_PATH, SlicedPath,
+    layout::{FileHeader, PathEntry, SizeEntry, TrieNode},
+};
 
 #[derive(Debug)]
 struct TreeNode<'a> {
@@ -22,14 +33,6 @@ struct TreeNode<'a> {
     name: &'a SlicedPath,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-struct Header1 {
-    compare_bit: u32,
-    id: u32,
-    left: u32,
-    right: u32,
-}
-
 fn find_first_bit_mismatch<'a>(
     first: u16,
     nodeless: &[&'a SlicedPath],
@@ -178,22 +181,24 @@ fn generate_tree(all_paths: &'_ [SlicedPath]) -> Vec<TreeNode<'_>> {
     nodes
 }
 
-pub struct Packer<'a> {
+pub struct Packer {
     rename_images: bool,
-    multi_progress: Option<&'a MultiProgress>,
+    progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    cancellation_token: Option<CancellationToken>,
 }
 
-impl Default for Packer<'_> {
+impl Default for Packer {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'a> Packer<'a> {
+impl Packer {
     pub const fn new() -> Self {
         Self {
             rename_images: false,
-            multi_progress: None,
+            progress_reporter: None,
+            cancellation_token: None,
         }
     }
 
@@ -204,20 +209,65 @@ impl<'a> Packer<'a> {
         }
     }
 
-    pub fn with_multi_progress(self, multi_progress: Option<&'a MultiProgress>) -> Self {
+    ///
+    /// Sets where the progress of the packing is reported. Defaults to
+    /// [`IndicatifProgress`](crate::helpers::progress::IndicatifProgress) if the `indicatif`
+    /// feature is enabled, and to nothing otherwise.
+    ///
+    pub fn with_progress_reporter(
+        self,
+        progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    ) -> Self {
         Self {
-            multi_progress,
+            progress_reporter,
             ..self
         }
     }
 
-    pub fn pack(&self, source_dir: &Path, target_file: &mut dyn WriteSeek) -> std::io::Result<()> {
-        let collecting_files_progress = ProgressBar::new_spinner()
-            .with_elapsed(Duration::from_secs(0))
-            .with_message("collecting all files...")
-            .with_style(default_spinner_style())
-            .in_optional_multi_progress(self.multi_progress);
-        collecting_files_progress.enable_steady_tick(Duration::from_millis(200));
+    #[cfg(feature = "indicatif")]
+    pub fn with_multi_progress(self, multi_progress: Option<&MultiProgress>) -> Self {
+        self.with_progress_reporter(Some(Arc::new(
+            crate::helpers::progress::IndicatifProgress::new(
+                multi_progress.cloned().unwrap_or_default(),
+            ),
+        )))
+    }
+
+    fn progress_reporter(&self) -> Arc<dyn ProgressReporter> {
+        self.progress_reporter
+            .clone()
+            .unwrap_or_else(default_progress_reporter)
+    }
+
+    ///
+    /// Sets a token that can be used to stop the packing between two files.
+    ///
+    /// The archive written so far is incomplete: use [`Packer::pack_to_path`] to make sure
+    /// it doesn't replace the target.
+    ///
+    pub fn with_cancellation_token(self, cancellation_token: Option<CancellationToken>) -> Self {
+        Self {
+            cancellation_token,
+            ..self
+        }
+    }
+
+    ///
+    /// Packs `source_dir` into a new archive at `target_path`.
+    ///
+    /// Unlike [`Packer::pack`], the archive is written to a temporary file that only replaces
+    /// `target_path` once it's complete, so a failure never leaves a corrupted archive behind.
+    ///
+    pub fn pack_to_path(&self, source_dir: &Path, target_path: &Path) -> io::Result<()> {
+        write_atomically(target_path, |target_file| {
+            self.pack(source_dir, target_file)
+        })
+    }
+
+    pub fn pack(&self, source_dir: &Path, target_file: &mut dyn WriteSeek) -> io::Result<()> {
+        let collecting_files_progress =
+            self.progress_reporter()
+                .start_phase("collecting files", None, ProgressUnit::Items);
 
         let all_paths = WalkDir::new(source_dir)
             .into_iter()
@@ -234,32 +284,53 @@ impl<'a> Packer<'a> {
             })
             .collect::<Vec<_>>();
 
-        write!(target_file, "MDB1")?;
-        target_file.write_u32::<LittleEndian>(all_paths.len() as u32 + 1)?;
-        target_file.write_u32::<LittleEndian>(all_paths.len() as u32 + 1)?;
-        target_file.write_u32::<LittleEndian>(all_paths.len() as u32)?;
+        collecting_files_progress.finish("finished collecting all files!");
 
-        let data_start_offset = all_paths.len() * (40 + 0x80) + 48 + 0x80;
-        // This is the data start offset and the total file size, but we don't know that yet
-        target_file.write_u64::<LittleEndian>(data_start_offset as u64)?;
-        target_file.write_u64::<LittleEndian>(0)?;
-
-        let tree = generate_tree(&all_paths);
+        self.pack_entries(&all_paths, target_file, |entry| {
+            let file_content = if self.rename_images && entry.extension == *b"img " {
+                fs::read(format!("{}/{}.dds", source_dir.display(), entry.file))?
+            } else {
+                fs::read(format!("{}/{}", source_dir.display(), entry))?
+            };
+            let compressed = lz4::block::compress(
+                &file_content,
+                //None,
+                Some(CompressionMode::HIGHCOMPRESSION(12)),
+                false,
+            )?;
+            Ok(CompressedFile {
+                content: compressed,
+                decompressed_size: file_content.len(),
+            })
+        })
+    }
 
-        target_file.write_u64::<LittleEndian>(u64::MAX)?;
-        target_file.write_u32::<LittleEndian>(0)?;
-        target_file.write_u32::<LittleEndian>(1)?;
+    ///
+    /// Writes an archive containing `all_paths`, calling `read_entry` to get the content
+    /// of each of them.
+    ///
+    pub(super) fn pack_entries(
+        &self,
+        all_paths: &[SlicedPath],
+        target_file: &mut dyn WriteSeek,
+        mut read_entry: impl FnMut(&SlicedPath) -> io::Result<CompressedFile>,
+    ) -> io::Result<()> {
+        // The total size isn't known until every file is written
+        let mut header = FileHeader::new(all_paths.len() as u32, 0);
+        header.write_to(target_file)?;
+
+        let tree = generate_tree(all_paths);
 
         let def_slice = SlicedPath::default();
 
-        let mut header_1s = vec![(Header1::default(), &def_slice); all_paths.len()];
+        let mut nodes = vec![(TrieNode::default(), &def_slice); all_paths.len()];
 
         for (i, path) in all_paths.iter().enumerate() {
             let position = tree[1..].iter().position(|x| path == x.name).unwrap();
             let entry = &tree[1..][position];
 
-            header_1s[position] = (
-                Header1 {
+            nodes[position] = (
+                TrieNode {
                     id: i as u32,
                     left: entry.left as u32,
                     right: entry.right as u32,
@@ -269,85 +340,53 @@ impl<'a> Packer<'a> {
             );
         }
 
-        for (entry, _) in &header_1s {
-            target_file.write_u32::<LittleEndian>(entry.compare_bit)?;
-            target_file.write_u32::<LittleEndian>(entry.id)?;
-            target_file.write_u32::<LittleEndian>(entry.left)?;
-            target_file.write_u32::<LittleEndian>(entry.right)?;
+        for (node, _) in &nodes {
+            node.write_to(target_file)?;
         }
 
-        const EMPTY_BUFFER: [u8; 0x80] = [0; 0x80];
-
-        target_file.write_all(&EMPTY_BUFFER)?;
-
-        for &(_, entry) in &header_1s {
-            target_file.write_all(&entry.extension)?;
-            target_file.write_all(entry.file.replace('/', "\\").as_bytes())?;
-            target_file
-                .write_all(&EMPTY_BUFFER[..0x80 - entry.extension.len() - entry.file.len()])?;
-        }
-
-        for _ in tree[1..].iter() {
-            target_file.write_u64::<LittleEndian>(0)?;
-            target_file.write_u64::<LittleEndian>(0)?;
-            target_file.write_u64::<LittleEndian>(0)?;
+        PathEntry::EMPTY.write_to(target_file)?;
+        for &(_, path) in &nodes {
+            PathEntry::new(path)?.write_to(target_file)?;
         }
 
-        struct FileEntry {
-            offset: u64,
-            uncompressed_size: u64,
-            compressed_size: u64,
+        // The sizes of the files are only known once they're written
+        for _ in &nodes {
+            SizeEntry::default().write_to(target_file)?;
         }
 
         let mut offset = 0;
-        let mut entries = Vec::new();
-
-        header_1s.sort_unstable_by_key(|(x, _)| x.id);
+        let mut sizes = Vec::new();
 
-        collecting_files_progress.finish_with_message("finished collecting all files!");
+        nodes.sort_unstable_by_key(|(x, _)| x.id);
 
-        let compression_progress = ProgressBar::new(header_1s.len() as u64)
-            .with_style(default_bar_style_with_message_header("compressing file"))
-            .with_finish(ProgressFinish::WithMessage(Cow::Borrowed(
-                "finished compressing all files",
-            )))
-            .in_optional_multi_progress(self.multi_progress);
+        let compression_progress = self.progress_reporter().start_phase(
+            "compressing file",
+            Some(nodes.len() as u64),
+            ProgressUnit::Items,
+        );
 
-        for (_, entry) in header_1s
-            .into_iter()
-            .progress_with(compression_progress.clone())
-        {
-            compression_progress.set_message(Cow::Owned(entry.to_string()));
-            let file_content = if self.rename_images && entry.extension == *b"img " {
-                fs::read(format!("{}/{}.dds", source_dir.display(), entry.file))?
-            } else {
-                fs::read(format!("{}/{}", source_dir.display(), entry))?
-            };
-            let compressed = lz4::block::compress(
-                &file_content,
-                //None,
-                Some(CompressionMode::HIGHCOMPRESSION(12)),
-                false,
-            )?;
-            entries.push(FileEntry {
+        for (_, entry) in nodes {
+            check_cancellation(self.cancellation_token.as_ref())?;
+            compression_progress.set_message(&entry.to_string());
+            let compressed = read_entry(entry)?;
+            sizes.push(SizeEntry {
                 offset,
-                uncompressed_size: file_content.len() as u64,
-                compressed_size: compressed.len() as u64,
+                decompressed_size: compressed.decompressed_size as u64,
+                compressed_size: compressed.content.len() as u64,
             });
-            offset += compressed.len() as u64;
-            target_file.write_all(&compressed)?;
+            offset += compressed.content.len() as u64;
+            target_file.write_all(&compressed.content)?;
+            compression_progress.advance(1);
         }
+        compression_progress.finish("finished compressing all files");
 
-        target_file.seek(SeekFrom::Start(0x18))?;
-        target_file.write_u64::<LittleEndian>(data_start_offset as u64 + offset)?;
+        header.total_size = header.data_start + offset;
+        target_file.seek(SeekFrom::Start(0))?;
+        header.write_to(target_file)?;
 
-        target_file.seek(SeekFrom::Start(
-            data_start_offset as u64 - all_paths.len() as u64 * 24,
-        ))?;
-        for entry in entries {
-            target_file.write_u64::<LittleEndian>(entry.offset)?;
-            target_file.write_u64::<LittleEndian>(entry.uncompressed_size)?;
-            target_file.write_u64::<LittleEndian>(entry.compressed_size)?;
+        target_file.seek(SeekFrom::Start(header.size_entries_start()))?;
+        for size in sizes {
+            size.write_to(target_file)?;
         }
 
         Ok(())
@@ -0,0 +1,130 @@
+use thl_tools::csv::merge::{MergeReport, MessageKey, NEEDS_REVIEW_MARKER, merge_translations};
+
+const HEADER: &str = "Call ID,Character Name,Translated,English,File\n";
+
+/// A CSV holding a dialogue of `a.mbe` for each `(id, translation, original)`.
+fn csv(rows: &[(&str, &str, &str)]) -> String {
+    let mut csv = HEADER.to_string();
+    for (id, translated, original) in rows {
+        csv.push_str(&format!("{id},Text,{translated},{original},a.mbe\n"));
+    }
+    csv
+}
+
+/// Merges the CSVs, returning the report and the `(id, translation)` of each merged row.
+fn merge(
+    old_original: &[(&str, &str, &str)],
+    new_original: &[(&str, &str, &str)],
+    translation: &[(&str, &str, &str)],
+) -> (MergeReport, Vec<(String, String)>) {
+    let mut merged = Vec::new();
+    let report = merge_translations(
+        &mut csv(old_original).as_bytes(),
+        &mut csv(new_original).as_bytes(),
+        &mut csv(translation).as_bytes(),
+        &mut merged,
+    )
+    .unwrap();
+    let rows = csv::Reader::from_reader(merged.as_slice())
+        .records()
+        .map(|x| {
+            let x = x.unwrap();
+            (x[0].to_string(), x[2].to_string())
+        })
+        .collect();
+    (report, rows)
+}
+
+fn key(id: &str) -> MessageKey {
+    MessageKey {
+        file_name: "a.mbe".to_string(),
+        id: id.to_string(),
+    }
+}
+
+fn row(id: &str, translated: &str) -> (String, String) {
+    (id.to_string(), translated.to_string())
+}
+
+fn marked(translated: &str) -> String {
+    format!("{NEEDS_REVIEW_MARKER}{translated}")
+}
+
+#[test]
+fn unchanged_dialogues_are_kept() {
+    let (report, rows) = merge(
+        &[("1", "", "Hello")],
+        &[("1", "", "Hello"), ("2", "", "New")],
+        &[("1", "Bonjour", "Hello")],
+    );
+    assert_eq!(rows, [row("1", "Bonjour"), row("2", "")]);
+    assert_eq!(
+        report,
+        MergeReport {
+            kept: 1,
+            untranslated: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn changed_dialogues_are_marked() {
+    let (report, rows) = merge(
+        &[("1", "", "Hello"), ("2", "", "Bye")],
+        &[("1", "", "Hello!"), ("2", "", "Bye!")],
+        &[
+            ("1", "Bonjour", "Hello"),
+            ("2", &marked("Au revoir"), "Bye"),
+        ],
+    );
+    // The marker isn't doubled
+    assert_eq!(
+        rows,
+        [row("1", &marked("Bonjour")), row("2", &marked("Au revoir"))]
+    );
+    assert_eq!(report.needs_review, [key("1"), key("2")]);
+    assert_eq!(report.kept, 0);
+}
+
+#[test]
+fn translations_without_old_original_are_marked() {
+    let (report, rows) = merge(&[], &[("1", "", "Hello")], &[("1", "Bonjour", "Hello")]);
+    assert_eq!(rows, [row("1", &marked("Bonjour"))]);
+    assert_eq!(report.needs_review, [key("1")]);
+    assert_eq!(report.untranslated, 0);
+}
+
+#[test]
+fn removed_dialogues_are_dropped() {
+    let (report, rows) = merge(
+        &[("1", "", "Hello"), ("2", "", "Bye")],
+        &[("1", "", "Hello")],
+        &[("1", "Bonjour", "Hello"), ("2", "Au revoir", "Bye")],
+    );
+    assert_eq!(rows, [row("1", "Bonjour")]);
+    assert_eq!(report.removed, 1);
+    assert_eq!(report.kept, 1);
+}
+
+#[test]
+fn duplicate_keys_are_matched_in_order() {
+    let (report, rows) = merge(
+        &[("1", "", "A"), ("1", "", "B")],
+        &[("1", "", "A"), ("1", "", "B!"), ("1", "", "C")],
+        &[("1", "Un", "A"), ("1", "Deux", "B")],
+    );
+    assert_eq!(
+        rows,
+        [row("1", "Un"), row("1", &marked("Deux")), row("1", "")]
+    );
+    assert_eq!(
+        report,
+        MergeReport {
+            kept: 1,
+            needs_review: vec![key("1")],
+            untranslated: 1,
+            removed: 0,
+        }
+    );
+}
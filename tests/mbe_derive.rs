@@ -0,0 +1,113 @@
+use std::io::Cursor;
+
+use byte_string::ByteString;
+use thl_tools::{
+    Character, PlaceholderOrCharacter,
+    helpers::offset_wrapper::{OffsetReadWrapper, OffsetWriteWrapper},
+    mbe::{ColumnType, MBEFile, RowMappingError},
+};
+use thl_tools_derive::{FromMbeRow, ToMbeRow};
+
+#[derive(Debug, Clone, PartialEq, FromMbeRow, ToMbeRow)]
+#[mbe(sheet = "message")]
+struct Line {
+    id: u32,
+    speaker: PlaceholderOrCharacter,
+    text: String,
+    note: Option<String>,
+}
+
+/// The columns of `message` in another order.
+#[derive(Debug, Clone, PartialEq, FromMbeRow, ToMbeRow)]
+struct Reordered {
+    #[mbe(column = 2)]
+    text: String,
+    #[mbe(column = 0)]
+    id: u32,
+    #[mbe(column = 3)]
+    note: Option<String>,
+    #[mbe(column = 1)]
+    speaker: PlaceholderOrCharacter,
+}
+
+fn lines() -> Vec<Line> {
+    vec![
+        Line {
+            id: 1,
+            speaker: PlaceholderOrCharacter::Character(Character::Text),
+            text: "Hello".to_string(),
+            note: None,
+        },
+        Line {
+            id: 2,
+            speaker: PlaceholderOrCharacter::Placeholder(0x1234),
+            text: "World".to_string(),
+            note: Some("a note".to_string()),
+        },
+    ]
+}
+
+/// A file with a `message` sheet holding `lines`, written and parsed back.
+fn written_file(lines: &[Line]) -> MBEFile {
+    let mut file = MBEFile::new();
+    let sheet = file
+        .add_sheet(
+            ByteString(b"message".to_vec()),
+            vec![
+                ColumnType::Int,
+                ColumnType::Int,
+                ColumnType::String,
+                ColumnType::String,
+            ],
+        )
+        .unwrap();
+    for (row, line) in lines.iter().enumerate() {
+        file.insert_mapped_row(sheet, row, line).unwrap();
+    }
+
+    let mut bytes = Vec::new();
+    file.write(&mut OffsetWriteWrapper::new(&mut bytes))
+        .unwrap();
+    MBEFile::parse(&mut OffsetReadWrapper::new(&mut Cursor::new(&bytes))).unwrap()
+}
+
+#[test]
+fn derived_rows_roundtrip() {
+    let file = written_file(&lines());
+    assert_eq!(file.read_sheet::<Line>().unwrap(), lines());
+}
+
+#[test]
+fn explicit_columns_roundtrip() {
+    let reordered = lines()
+        .into_iter()
+        .map(|x| Reordered {
+            text: x.text,
+            id: x.id,
+            note: x.note,
+            speaker: x.speaker,
+        })
+        .collect::<Vec<_>>();
+
+    let mut file = written_file(&[]);
+    for (row, x) in reordered.iter().enumerate() {
+        file.insert_mapped_row(0, row, x).unwrap();
+    }
+    assert_eq!(file.read_sheet::<Line>().unwrap(), lines());
+    assert_eq!(
+        file.get_sheet_by_index(0)
+            .unwrap()
+            .read_rows::<Reordered>()
+            .unwrap(),
+        reordered
+    );
+}
+
+#[test]
+fn read_sheet_needs_a_sheet_name() {
+    let file = written_file(&lines());
+    assert!(matches!(
+        file.read_sheet::<Reordered>(),
+        Err(RowMappingError::NoSheetName(_))
+    ));
+}
@@ -0,0 +1,74 @@
+use std::io::Cursor;
+
+use byte_string::ByteString;
+use thl_tools::{
+    helpers::offset_wrapper::{OffsetReadWrapper, OffsetWriteWrapper},
+    mbe::{ColumnType, MBEFile, TableCell, TableCreateCell},
+};
+
+/// The value of a column type the crate doesn't know.
+const UNKNOWN_TYPE: u32 = 3;
+
+/// A file with a sheet holding one row of an ID, a number and a string, written and parsed
+/// back. If `unknown` is set, the number is in a column of an unknown type.
+fn file(number: u32, unknown: bool) -> MBEFile {
+    let mut file = MBEFile::new();
+    let sheet = file
+        .add_sheet(
+            ByteString(b"messages".to_vec()),
+            vec![ColumnType::Int, ColumnType::Int, ColumnType::String],
+        )
+        .unwrap();
+    file.insert_row(
+        sheet,
+        0,
+        vec![
+            TableCreateCell::Int(1),
+            TableCreateCell::Int(number),
+            TableCreateCell::String(b"text".as_slice().into()),
+        ],
+    )
+    .unwrap();
+
+    let mut bytes = Vec::new();
+    file.write(&mut OffsetWriteWrapper::new(&mut bytes))
+        .unwrap();
+    if unknown {
+        let column_types = [2u32, 2, 7].map(u32::to_le_bytes).concat();
+        let start = bytes
+            .windows(column_types.len())
+            .position(|x| x == column_types)
+            .unwrap();
+        bytes[start + 4..start + 8].copy_from_slice(&UNKNOWN_TYPE.to_le_bytes());
+    }
+    MBEFile::parse(&mut OffsetReadWrapper::new(&mut Cursor::new(&bytes))).unwrap()
+}
+
+fn number(file: &MBEFile) -> TableCell<'_> {
+    file.get_sheet_by_index(0)
+        .unwrap()
+        .get_row(0)
+        .unwrap()
+        .get_column(1)
+        .unwrap()
+}
+
+#[test]
+fn rows_differing_in_a_number_are_replaced() {
+    let patched = file(10, false).patch(file(11, false)).unwrap();
+    assert_eq!(number(&patched), TableCell::Int(11));
+}
+
+#[test]
+fn rows_differing_in_an_unknown_field_are_replaced() {
+    let original = file(10, true);
+    assert_eq!(
+        number(&original),
+        TableCell::Unknown(UNKNOWN_TYPE, &10u32.to_le_bytes())
+    );
+    let patched = original.patch(file(11, true)).unwrap();
+    assert_eq!(
+        number(&patched),
+        TableCell::Unknown(UNKNOWN_TYPE, &11u32.to_le_bytes())
+    );
+}
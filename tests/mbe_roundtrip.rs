@@ -0,0 +1,132 @@
+use std::io::Cursor;
+
+use byte_string::ByteString;
+use thl_tools::{
+    helpers::offset_wrapper::{OffsetReadWrapper, OffsetWriteWrapper},
+    mbe::{ColumnType, MBEFile, TableCreateCell, verify_roundtrip},
+};
+
+fn write(file: &MBEFile) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    file.write(&mut OffsetWriteWrapper::new(&mut bytes))
+        .unwrap();
+    bytes
+}
+
+/// A file with a sheet of two strings per row, and its `CHNK` entries in offset order.
+fn sample() -> Vec<u8> {
+    let mut file = MBEFile::new();
+    let sheet = file
+        .add_sheet(
+            ByteString(b"messages".to_vec()),
+            vec![ColumnType::Int, ColumnType::String, ColumnType::String],
+        )
+        .unwrap();
+    for (row, (first, second)) in [("a", "first"), ("bc", "second one")].iter().enumerate() {
+        file.insert_row(
+            sheet,
+            row,
+            vec![
+                TableCreateCell::Int(row as u32),
+                TableCreateCell::String(first.as_bytes().into()),
+                TableCreateCell::String(second.as_bytes().into()),
+            ],
+        )
+        .unwrap();
+    }
+    write(&file)
+}
+
+/// The start and end of each entry of the `CHNK` section of `bytes`.
+fn chunk_entries(bytes: &[u8]) -> Vec<(usize, usize)> {
+    let start = bytes.windows(4).position(|x| x == b"CHNK").unwrap();
+    let count = u32::from_le_bytes(bytes[start + 4..start + 8].try_into().unwrap());
+    let mut offset = start + 8;
+    (0..count)
+        .map(|_| {
+            let length = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+            let entry = (offset, offset + 8 + length as usize);
+            offset = entry.1;
+            entry
+        })
+        .collect()
+}
+
+/// `bytes` with the entries of its `CHNK` section in reverse order.
+fn reverse_chunk_entries(bytes: &[u8]) -> Vec<u8> {
+    let entries = chunk_entries(bytes);
+    let (start, end) = (entries[0].0, entries[entries.len() - 1].1);
+    let mut reversed = bytes[..start].to_vec();
+    for &(entry_start, entry_end) in entries.iter().rev() {
+        reversed.extend_from_slice(&bytes[entry_start..entry_end]);
+    }
+    reversed.extend_from_slice(&bytes[end..]);
+    reversed
+}
+
+#[test]
+fn written_file_roundtrips() {
+    verify_roundtrip(&sample()).unwrap();
+}
+
+#[test]
+fn unsorted_chunk_entries_roundtrip() {
+    let bytes = reverse_chunk_entries(&sample());
+    assert_ne!(bytes, sample());
+    verify_roundtrip(&bytes).unwrap();
+}
+
+/// The offset given by each entry of the `CHNK` section of `bytes`.
+fn chunk_offsets(bytes: &[u8]) -> Vec<u32> {
+    chunk_entries(bytes)
+        .into_iter()
+        .map(|(start, _)| u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap()))
+        .collect()
+}
+
+/// `bytes` parsed, with a row inserted at position `row` of its sheet, and written back.
+fn insert_row(bytes: &[u8], row: usize) -> Vec<u8> {
+    let mut file = MBEFile::parse(&mut OffsetReadWrapper::new(&mut Cursor::new(bytes))).unwrap();
+    file.insert_row(
+        0,
+        row,
+        vec![
+            TableCreateCell::Int(2),
+            TableCreateCell::String(b"new".as_slice().into()),
+            TableCreateCell::String(b"newer".as_slice().into()),
+        ],
+    )
+    .unwrap();
+    write(&file)
+}
+
+#[test]
+fn new_strings_are_inserted_by_offset() {
+    let written = insert_row(&sample(), 1);
+    let offsets = chunk_offsets(&written);
+    assert_eq!(offsets.len(), 6);
+    assert!(offsets.is_sorted());
+    assert_eq!(
+        chunk_entries(&written)
+            .into_iter()
+            .map(|(start, end)| &written[start + 8..end])
+            .filter(|x| x.starts_with(b"new"))
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn parsed_strings_keep_their_order() {
+    let bytes = reverse_chunk_entries(&sample());
+    let written = insert_row(&bytes, 1);
+    let offsets = chunk_offsets(&written);
+    assert_eq!(offsets.len(), 6);
+
+    // The parsed entries are still reversed, the new ones are before the first entry with a
+    // greater offset, which is the first one
+    let mut parsed = offsets[2..].to_vec();
+    parsed.reverse();
+    assert!(parsed.is_sorted());
+    assert!(offsets[0] < offsets[1] && offsets[1] < offsets[2]);
+}
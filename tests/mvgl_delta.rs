@@ -0,0 +1,129 @@
+use std::{fs, io::Cursor, path::Path, sync::Arc};
+
+use tempfile::TempDir;
+use thl_tools::{
+    helpers::{
+        cancellation::{CancellationToken, Cancelled},
+        progress::NoProgress,
+    },
+    mvgl::{
+        MVGLArchive, Manifest, Packer,
+        delta::{Delta, DeltaApplier, DeltaError},
+    },
+};
+
+/// A directory holding `files`.
+fn directory(files: &[(&str, &[u8])]) -> TempDir {
+    let directory = TempDir::new().unwrap();
+    for (path, content) in files {
+        let path = directory.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    directory
+}
+
+/// The bytes of the archive packing `directory`.
+fn pack(directory: &Path) -> Vec<u8> {
+    let mut archive = Cursor::new(Vec::new());
+    Packer::new()
+        .with_progress_reporter(Some(Arc::new(NoProgress)))
+        .pack(directory, &mut archive)
+        .unwrap();
+    archive.into_inner()
+}
+
+fn open(archive: &[u8]) -> MVGLArchive<Cursor<&[u8]>> {
+    MVGLArchive::from_reader(Cursor::new(archive)).unwrap()
+}
+
+fn vanilla() -> Vec<u8> {
+    pack(
+        directory(&[
+            ("text/a.mbe", b"first file"),
+            ("text/b.mbe", b"second file"),
+            ("images/c.img", b"an image"),
+        ])
+        .path(),
+    )
+}
+
+fn modified() -> Vec<u8> {
+    pack(
+        directory(&[
+            ("text/a.mbe", b"first file, translated"),
+            ("images/c.img", b"an image"),
+            ("text/d.mbe", b"a new file"),
+        ])
+        .path(),
+    )
+}
+
+fn applier() -> DeltaApplier {
+    DeltaApplier::new().with_progress_reporter(Some(Arc::new(NoProgress)))
+}
+
+/// `delta` after being written and read back.
+fn roundtrip(delta: &Delta) -> Delta {
+    let mut bytes = Vec::new();
+    delta.write(&mut bytes).unwrap();
+    Delta::read(&mut bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn applied_delta_rebuilds_the_modified_archive() {
+    let (vanilla, modified) = (vanilla(), modified());
+    let delta = roundtrip(&Delta::create(&open(&vanilla), &open(&modified)).unwrap());
+    assert_eq!(delta.entries().len(), 3);
+
+    let mut patched = Cursor::new(Vec::new());
+    applier()
+        .apply(&delta, &open(&vanilla), &mut patched)
+        .unwrap();
+    let expected = Manifest::from_archive(&open(&modified)).unwrap();
+    assert_eq!(
+        expected.verify_archive(&open(patched.get_ref())).unwrap(),
+        []
+    );
+}
+
+#[test]
+fn wrong_vanilla_archive_is_rejected() {
+    let vanilla = vanilla();
+    let delta = Delta::create(&open(&vanilla), &open(&modified())).unwrap();
+
+    let other = pack(directory(&[("text/a.mbe", b"another file")]).path());
+    let mut patched = Cursor::new(Vec::new());
+    assert!(matches!(
+        applier().apply(&delta, &open(&other), &mut patched),
+        Err(DeltaError::HashMismatch { path, .. }) if path == "text/a.mbe"
+    ));
+    assert!(patched.get_ref().is_empty());
+
+    // Adding an entry the archive already has would duplicate it
+    let added_only = Delta::create(
+        &open(&pack(directory(&[("text/a.mbe", b"first file")]).path())),
+        &open(&vanilla),
+    )
+    .unwrap();
+    assert!(matches!(
+        applier().apply(&added_only, &open(&vanilla), &mut patched),
+        Err(DeltaError::ExistingEntry(path)) if path == "images/c.img" || path == "text/b.mbe"
+    ));
+}
+
+#[test]
+fn cancelled_apply_stops() {
+    let (vanilla, modified) = (vanilla(), modified());
+    let delta = Delta::create(&open(&vanilla), &open(&modified)).unwrap();
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut patched = Cursor::new(Vec::new());
+    assert!(matches!(
+        applier()
+            .with_cancellation_token(Some(token))
+            .apply(&delta, &open(&vanilla), &mut patched),
+        Err(DeltaError::Io(error)) if Cancelled::is(&error)
+    ));
+}
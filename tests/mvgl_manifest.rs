@@ -0,0 +1,115 @@
+use std::{fs, io::Cursor, path::Path};
+
+use tempfile::TempDir;
+use thl_tools::mvgl::{Extractor, MVGLArchive, Manifest, ManifestMismatch, Packer};
+
+/// A directory holding a few files, an image among them.
+fn sample_directory() -> TempDir {
+    let directory = TempDir::new().unwrap();
+    let files: [(&str, &[u8]); 3] = [
+        ("text/a.mbe", b"first file"),
+        ("text/sub/b.mbe", b"second file, a bit longer"),
+        ("images/c.img", b"an image"),
+    ];
+    for (path, content) in files {
+        let path = directory.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    directory
+}
+
+/// The bytes of the archive packing `directory`.
+fn pack(directory: &Path) -> Vec<u8> {
+    let mut archive = Cursor::new(Vec::new());
+    Packer::new().pack(directory, &mut archive).unwrap();
+    archive.into_inner()
+}
+
+fn open(archive: &[u8]) -> MVGLArchive<Cursor<&[u8]>> {
+    MVGLArchive::from_reader(Cursor::new(archive)).unwrap()
+}
+
+fn sorted(mismatches: Vec<ManifestMismatch>) -> Vec<String> {
+    let mut mismatches = mismatches
+        .iter()
+        .map(ManifestMismatch::to_string)
+        .collect::<Vec<_>>();
+    mismatches.sort();
+    mismatches
+}
+
+#[test]
+fn unchanged_archive_matches() {
+    let directory = sample_directory();
+    let manifest = Manifest::from_archive(&open(&pack(directory.path()))).unwrap();
+    assert_eq!(manifest.entries().len(), 3);
+    assert_eq!(
+        manifest
+            .verify_archive(&open(&pack(directory.path())))
+            .unwrap(),
+        []
+    );
+}
+
+#[test]
+fn tampered_archive_is_reported() {
+    let directory = sample_directory();
+    let manifest = Manifest::from_archive(&open(&pack(directory.path()))).unwrap();
+
+    fs::write(directory.path().join("text/a.mbe"), b"first fil3").unwrap();
+    fs::write(directory.path().join("images/c.img"), b"a longer image").unwrap();
+    fs::remove_file(directory.path().join("text/sub/b.mbe")).unwrap();
+    fs::write(directory.path().join("text/d.mbe"), b"new").unwrap();
+    let mismatches = sorted(
+        manifest
+            .verify_archive(&open(&pack(directory.path())))
+            .unwrap(),
+    );
+    assert_eq!(mismatches.len(), 4);
+    assert!(mismatches[0].starts_with("images/c.img: expected 8 bytes, found 14"));
+    assert!(mismatches[1].starts_with("text/a.mbe: expected hash"));
+    assert_eq!(mismatches[2], "text/d.mbe: not in manifest");
+    assert_eq!(mismatches[3], "text/sub/b.mbe: missing");
+}
+
+#[test]
+fn extracted_directory_matches() {
+    let archive = pack(sample_directory().path());
+    let manifest = Manifest::from_archive(&open(&archive)).unwrap();
+    for rename_images in [false, true] {
+        let extracted = TempDir::new().unwrap();
+        Extractor::new()
+            .with_rename_images(rename_images)
+            .extract(&mut Cursor::new(&archive), extracted.path())
+            .unwrap();
+        assert_eq!(
+            manifest
+                .verify_directory(extracted.path(), rename_images)
+                .unwrap(),
+            []
+        );
+    }
+}
+
+#[test]
+fn tampered_directory_is_reported() {
+    let archive = pack(sample_directory().path());
+    let manifest = Manifest::from_archive(&open(&archive)).unwrap();
+    let extracted = TempDir::new().unwrap();
+    Extractor::new()
+        .with_rename_images(true)
+        .extract(&mut Cursor::new(&archive), extracted.path())
+        .unwrap();
+
+    // Renamed images only match if the directory is known to have them
+    assert_eq!(
+        sorted(manifest.verify_directory(extracted.path(), false).unwrap()),
+        ["images/c.dds: not in manifest", "images/c.img: missing"]
+    );
+
+    fs::write(extracted.path().join("text/a.mbe"), b"first fil3").unwrap();
+    let mismatches = sorted(manifest.verify_directory(extracted.path(), true).unwrap());
+    assert_eq!(mismatches.len(), 1);
+    assert!(mismatches[0].starts_with("text/a.mbe: expected hash"));
+}
@@ -0,0 +1,208 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, LitInt, LitStr, parse_macro_input, spanned::Spanned};
+
+/// A field of the struct, with the column it's mapped to.
+struct MappedField {
+    /// How the field is accessed, its name or its index.
+    member: syn::Member,
+    name: String,
+    ty: syn::Type,
+    column: usize,
+}
+
+struct MappedStruct {
+    sheet: Option<String>,
+    fields: Vec<MappedField>,
+    /// Whether a field was given its column, in which case the other columns may not be
+    /// mapped.
+    explicit_columns: bool,
+}
+
+fn parse_struct(input: &DeriveInput) -> syn::Result<MappedStruct> {
+    let mut sheet = None;
+    for attribute in input.attrs.iter().filter(|x| x.path().is_ident("mbe")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("sheet") {
+                sheet = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `sheet = \"...\"`"))
+            }
+        })?;
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "rows can only be mapped to structs",
+        ));
+    };
+    let fields = match &data.fields {
+        Fields::Named(x) => x.named.iter().collect::<Vec<_>>(),
+        Fields::Unnamed(x) => x.unnamed.iter().collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let mut explicit_columns = false;
+    let fields = fields
+        .into_iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let mut column = index;
+            for attribute in field.attrs.iter().filter(|x| x.path().is_ident("mbe")) {
+                attribute.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("column") {
+                        column = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                        explicit_columns = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `column = <index>`"))
+                    }
+                })?;
+            }
+            let (member, name) = match &field.ident {
+                Some(x) => (syn::Member::Named(x.clone()), x.to_string()),
+                None => (syn::Member::Unnamed(index.into()), index.to_string()),
+            };
+            Ok(MappedField {
+                member,
+                name,
+                ty: field.ty.clone(),
+                column,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(MappedStruct {
+        sheet,
+        fields,
+        explicit_columns,
+    })
+}
+
+fn derive_from_mbe_row(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mapped = parse_struct(&input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let sheet = match &mapped.sheet {
+        Some(x) => quote!(::core::option::Option::Some(#x)),
+        None => quote!(::core::option::Option::None),
+    };
+    let check_length = (!mapped.explicit_columns).then(|| {
+        let length = mapped.fields.len();
+        quote!(::thl_tools::mbe::__private::check_length(column_types, #length)?;)
+    });
+    let checks = mapped.fields.iter().map(|field| {
+        let MappedField {
+            name, ty, column, ..
+        } = field;
+        quote!(::thl_tools::mbe::__private::check_field::<#ty>(column_types, #column, #name)?;)
+    });
+    let reads = mapped.fields.iter().map(|field| {
+        let MappedField {
+            member,
+            name,
+            column,
+            ..
+        } = field;
+        quote!(#member: ::thl_tools::mbe::__private::read_field(row, #column, #name)?)
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::thl_tools::mbe::FromMbeRow for #ident #ty_generics #where_clause {
+            const SHEET: ::core::option::Option<&'static str> = #sheet;
+
+            fn check_column_types(
+                column_types: &[::thl_tools::mbe::ColumnType],
+            ) -> ::core::result::Result<(), ::thl_tools::mbe::RowMappingError> {
+                #check_length
+                #(#checks)*
+                ::core::result::Result::Ok(())
+            }
+
+            fn from_mbe_row(
+                row: &[::thl_tools::mbe::TableCell<'_>],
+            ) -> ::core::result::Result<Self, ::thl_tools::mbe::RowMappingError> {
+                ::core::result::Result::Ok(Self { #(#reads),* })
+            }
+        }
+    })
+}
+
+fn derive_to_mbe_row(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mapped = parse_struct(&input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let length = mapped.fields.len();
+
+    // Every column must be written, so fields are put in the order of their columns
+    let mut fields = mapped.fields.iter().collect::<Vec<_>>();
+    fields.sort_by_key(|x| x.column);
+    if let Some(field) = fields
+        .iter()
+        .enumerate()
+        .find(|&(i, field)| field.column != i)
+        .map(|(_, field)| field)
+    {
+        return Err(syn::Error::new(
+            field.ty.span(),
+            "every column must be mapped to exactly one field",
+        ));
+    }
+    let cells = fields.iter().enumerate().map(|(i, field)| {
+        let MappedField {
+            member,
+            name,
+            column,
+            ..
+        } = field;
+        let variable = format_ident!("cell_{i}");
+        (
+            variable.clone(),
+            quote!(let #variable = ::thl_tools::mbe::__private::write_field(&self.#member, column_types, #column, #name)?;),
+        )
+    });
+    let (variables, writes): (Vec<_>, Vec<_>) = cells.unzip();
+
+    Ok(quote! {
+        impl #impl_generics ::thl_tools::mbe::ToMbeRow for #ident #ty_generics #where_clause {
+            fn to_mbe_row(
+                &self,
+                column_types: &[::thl_tools::mbe::ColumnType],
+            ) -> ::core::result::Result<
+                ::std::vec::Vec<::thl_tools::mbe::TableCreateCell<'_>>,
+                ::thl_tools::mbe::RowMappingError,
+            > {
+                ::thl_tools::mbe::__private::check_length(column_types, #length)?;
+                #(#writes)*
+                ::core::result::Result::Ok(::std::vec![#(#variables),*])
+            }
+        }
+    })
+}
+
+///
+/// Implements `FromMbeRow`, mapping the fields of a struct to the columns of a sheet.
+///
+/// The sheet is named with `#[mbe(sheet = "...")]` on the struct. Fields are mapped to the
+/// columns in order, unless they are given one with `#[mbe(column = 2)]`.
+///
+#[proc_macro_derive(FromMbeRow, attributes(mbe))]
+pub fn from_mbe_row(input: TokenStream) -> TokenStream {
+    derive_from_mbe_row(parse_macro_input!(input as DeriveInput))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+///
+/// Implements `ToMbeRow`. Fields are mapped like with `FromMbeRow`, and every column must
+/// be mapped to exactly one field.
+///
+#[proc_macro_derive(ToMbeRow, attributes(mbe))]
+pub fn to_mbe_row(input: TokenStream) -> TokenStream {
+    derive_to_mbe_row(parse_macro_input!(input as DeriveInput))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}